@@ -0,0 +1,87 @@
+//! 发送端的地址簿和最近发送过的目标，保存在配置目录下
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+/// 最近目标最多保留的条数
+const MAX_RECENT: usize = 8;
+
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AddressBook {
+    pub peers: Vec<SavedPeer>,
+    /// 最近成功发送过的目标，新的在前
+    pub recent: Vec<Destination>,
+}
+
+/// 用户命名保存的接收端
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SavedPeer {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Destination {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Destination {
+    /// 解析 `host:port`，IPv6 地址可以带方括号
+    pub fn parse(addr: &str) -> Option<Self> {
+        let (host, port) = addr.rsplit_once(':')?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return None;
+        }
+        Some(Self {
+            host: host.to_string(),
+            port: port.parse().ok()?,
+        })
+    }
+}
+
+impl AddressBook {
+    /// 移到最前面，同一个目标只保留一条
+    pub fn add_recent(&mut self, destination: Destination) {
+        self.recent.retain(|d| *d != destination);
+        self.recent.insert(0, destination);
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// 已有同名条目时替换它
+    pub fn save_peer(&mut self, peer: SavedPeer) {
+        match self.peers.iter_mut().find(|p| p.name == peer.name) {
+            Some(old) => *old = peer,
+            None => self.peers.push(peer),
+        }
+        self.peers.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+pub fn address_book_path() -> Option<PathBuf> {
+    Some(
+        dirs::config_dir()?
+            .join("transfer-dioxus")
+            .join("address_book.json"),
+    )
+}
+
+/// 文件不存在或无法解析时返回空的地址簿
+pub fn load() -> AddressBook {
+    address_book_path()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(book: &AddressBook) -> anyhow::Result<()> {
+    let path = address_book_path().ok_or_else(|| anyhow::anyhow!("找不到配置目录"))?;
+    fs::create_dir_all(path.parent().unwrap_or(&path))?;
+
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(book)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
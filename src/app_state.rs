@@ -1,18 +1,40 @@
-use crate::form_field::{use_form_field, FormField};
+use crate::{
+    address_book::{self, AddressBook, Destination},
+    form_field::{use_form_field, FormField},
+    history::{self, TransferRecord},
+    receiver::{ConflictPolicy, EventSink, Prompt, TransferEvent},
+    sender::{browse, LinkMode, Peer, RateLimit, Target, TransferControl},
+    settings::Settings,
+    transfer_protocol::progress::Progress,
+};
 use dioxus::prelude::*;
+use futures_util::StreamExt;
 use rust_i18n::t;
+use serde::{Deserialize, Serialize};
 use std::{
     fmt::Display,
-    net::{IpAddr, Ipv4Addr},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     path::PathBuf,
     sync::{atomic::AtomicBool, Arc},
 };
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub enum Language {
+    #[default]
+    #[serde(rename = "en")]
     English,
+    #[serde(rename = "zh")]
     Chinese,
 }
+impl Language {
+    /// rust-i18n 使用的语言代码
+    pub fn locale(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Chinese => "zh",
+        }
+    }
+}
 impl Display for Language {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -22,6 +44,9 @@ impl Display for Language {
     }
 }
 
+/// 接收端某个连接的最新进度，`None` 表示连接已结束
+pub type ProgressUpdate = (SocketAddr, Option<Progress>);
+
 #[derive(Clone)]
 pub struct ReceiverState {
     pub port_field: FormField<u16>,
@@ -29,55 +54,262 @@ pub struct ReceiverState {
     pub logs: Signal<Vec<String>>,
     pub is_running: Signal<Arc<AtomicBool>>,
     pub log_tx: Signal<UnboundedSender<String>>,
+    /// 等待用户回答的传输清单和文件冲突，按到达顺序排队
+    pub prompts: Signal<Vec<Prompt>>,
+    pub prompt_tx: Signal<UnboundedSender<Prompt>>,
+    /// 服务运行期间显示给发送方的一次性配对码
+    pub pairing_code: Signal<String>,
+    pub code_tx: Signal<UnboundedSender<String>>,
+    /// 正在进行的连接的进度，每个对端一项
+    pub transfers: Signal<Vec<Progress>>,
+    pub progress_tx: Signal<UnboundedSender<ProgressUpdate>>,
+    /// 接收限速，单位 MB/s，0 表示不限速
+    pub rate_limit_field: FormField<f64>,
+    pub rate_limit: Signal<Arc<RateLimit>>,
+    /// 保存路径上已有同名文件时的处理方式
+    pub conflict_policy: Signal<ConflictPolicy>,
 }
 pub fn use_receiver_state(
     log_tx: Signal<UnboundedSender<String>>,
     logs: Signal<Vec<String>>,
+    prompt_tx: Signal<UnboundedSender<Prompt>>,
+    prompts: Signal<Vec<Prompt>>,
+    code_tx: Signal<UnboundedSender<String>>,
+    pairing_code: Signal<String>,
+    settings: &Settings,
 ) -> ReceiverState {
-    let port_field = use_form_field(8000_u16, |s| s.parse().map_err(|_| t!("port_validation")));
+    let port_field = use_form_field(settings.receiver_port, |s| {
+        s.parse().map_err(|_| t!("port_validation"))
+    });
+    let (rate_limit_field, rate_limit) = use_rate_limit(settings.receive_limit);
+    let (progress_tx, transfers) = use_transfers();
 
     ReceiverState {
         port_field,
-        dir: Signal::new(std::fs::canonicalize(".").unwrap()),
+        dir: Signal::new(settings.save_dir.clone()),
         logs,
         is_running: Signal::new(Arc::new(AtomicBool::new(false))),
         log_tx,
+        prompts,
+        prompt_tx,
+        pairing_code,
+        code_tx,
+        transfers,
+        progress_tx,
+        rate_limit_field,
+        rate_limit,
+        conflict_policy: Signal::new(settings.conflict_policy),
     }
 }
 
 #[derive(Clone)]
 pub struct SenderState {
-    pub ip_field: FormField<IpAddr>,
+    /// 主机名或 IP，可以带端口
+    pub ip_field: FormField<Target>,
     pub port_field: FormField<u16>,
     pub enable_directory: Signal<bool>,
+    pub link_mode: Signal<LinkMode>,
     pub file: Signal<PathBuf>,
+    pub pairing_code: Signal<String>,
     pub logs: Signal<Vec<String>>,
     pub is_running: Signal<Arc<AtomicBool>>,
+    /// 上一次发送的目标地址和路径，失败后用于续传
+    pub last_send: Signal<Option<(Target, PathBuf)>>,
+    pub resumable: Signal<Arc<AtomicBool>>,
+    /// 当前发送的控制状态，每次开始发送时换新
+    pub control: Signal<Arc<TransferControl>>,
+    /// 发送限速，单位 MB/s，0 表示不限速
+    pub rate_limit_field: FormField<f64>,
+    pub rate_limit: Signal<Arc<RateLimit>>,
     pub log_tx: Signal<UnboundedSender<String>>,
-    pub progress_tx: Signal<UnboundedSender<(f64, String)>>,
-    pub progress: Signal<(f64, String)>,
+    pub progress_tx: Signal<UnboundedSender<Progress>>,
+    pub progress: Signal<Progress>,
+    /// 局域网内发现的接收端
+    pub peers: Signal<Vec<Peer>>,
+    /// 地址簿和最近发送过的目标，修改后自动保存
+    pub address_book: Signal<AddressBook>,
+    /// 发送成功后把目标地址发到这里，记入最近目标
+    pub recent_tx: Signal<UnboundedSender<String>>,
 }
 
 pub fn use_sender_state(
     log_tx: Signal<UnboundedSender<String>>,
     logs: Signal<Vec<String>>,
-    progress_tx: Signal<UnboundedSender<(f64, String)>>,
-    progress: Signal<(f64, String)>,
+    progress_tx: Signal<UnboundedSender<Progress>>,
+    progress: Signal<Progress>,
+    settings: &Settings,
 ) -> SenderState {
-    let port_field = use_form_field(8000_u16, |s| s.parse().map_err(|_| t!("port_validation")));
-    let ip_field = use_form_field(Ipv4Addr::LOCALHOST.into(), |s| {
-        s.parse().map_err(|_| t!("invalid_ip"))
+    let port_field = use_form_field(settings.sender_port, |s| {
+        s.parse().map_err(|_| t!("port_validation"))
+    });
+    let ip_field = use_form_field(IpAddr::from(Ipv4Addr::LOCALHOST).into(), |s| {
+        s.parse::<Target>()
     });
+    let (rate_limit_field, rate_limit) = use_rate_limit(settings.send_limit);
+    let (address_book, recent_tx) = use_address_book(log_tx);
 
     SenderState {
         ip_field,
         port_field,
         enable_directory: use_signal(|| false),
+        link_mode: use_signal(LinkMode::default),
         file: use_signal(PathBuf::new),
+        pairing_code: use_signal(String::new),
         logs,
         is_running: use_signal(|| Arc::new(AtomicBool::new(false))),
+        last_send: use_signal(|| None),
+        resumable: use_signal(|| Arc::new(AtomicBool::new(false))),
+        control: use_signal(|| Arc::new(TransferControl::default())),
+        rate_limit_field,
+        rate_limit,
         log_tx,
         progress_tx,
         progress,
+        peers: use_peers(),
+        address_book,
+        recent_tx,
     }
 }
+
+/// 把接收线程的事件转给日志、提问、配对码和进度通道，连接结束时进度通道收到 `None`
+pub fn receiver_events(
+    log_tx: UnboundedSender<String>,
+    prompt_tx: UnboundedSender<Prompt>,
+    code_tx: UnboundedSender<String>,
+    progress_tx: UnboundedSender<ProgressUpdate>,
+) -> Arc<dyn EventSink> {
+    Arc::new(move |event: TransferEvent| match event {
+        TransferEvent::Progress(progress) => {
+            if let Some(peer) = progress.peer {
+                _ = progress_tx.unbounded_send((peer, Some(progress)));
+            }
+        }
+        TransferEvent::Closed { peer } => _ = progress_tx.unbounded_send((peer, None)),
+        TransferEvent::Record(record) => save_record(&record, &log_tx),
+        TransferEvent::Prompt(prompt) => _ = prompt_tx.unbounded_send(prompt),
+        TransferEvent::PairingCode(code) => _ = code_tx.unbounded_send(code),
+        event => {
+            if let Some(line) = event.log_line() {
+                _ = log_tx.unbounded_send(line);
+            }
+        }
+    })
+}
+
+/// 把发送线程的事件转给日志和进度通道
+pub fn sender_events(
+    log_tx: UnboundedSender<String>,
+    progress_tx: UnboundedSender<Progress>,
+) -> Arc<dyn EventSink> {
+    Arc::new(move |event: TransferEvent| match event {
+        TransferEvent::Progress(progress) => _ = progress_tx.unbounded_send(progress),
+        TransferEvent::Record(record) => save_record(&record, &log_tx),
+        event => {
+            if let Some(line) = event.log_line() {
+                _ = log_tx.unbounded_send(line);
+            }
+        }
+    })
+}
+
+/// 写入传输历史，失败只记进日志，不影响传输
+fn save_record(record: &TransferRecord, log_tx: &UnboundedSender<String>) {
+    if let Err(e) = history::append(record) {
+        _ = log_tx.unbounded_send(format!("{} : {e}", t!("history_fail")));
+    }
+}
+
+/// 在后台一直监听局域网内的接收端通告
+fn use_peers() -> Signal<Vec<Peer>> {
+    let mut peers = use_signal(Vec::new);
+    let peers_tx = use_coroutine(move |mut rx: UnboundedReceiver<Vec<Peer>>| async move {
+        while let Some(list) = rx.next().await {
+            peers.set(list);
+        }
+    });
+
+    use_hook(|| {
+        let peers_tx = peers_tx.tx();
+        std::thread::spawn(move || {
+            // 端口被占用或没有可用网络时只是没有发现功能，手动输入地址仍然可用
+            _ = browse(Arc::new(AtomicBool::new(true)), |list| {
+                _ = peers_tx.unbounded_send(list);
+            });
+        });
+    });
+
+    peers
+}
+
+/// 启动时读取地址簿，之后每次修改都写回文件
+fn use_address_book(
+    log_tx: Signal<UnboundedSender<String>>,
+) -> (Signal<AddressBook>, Signal<UnboundedSender<String>>) {
+    let mut address_book = use_signal(address_book::load);
+    let recent_tx = use_coroutine(move |mut rx: UnboundedReceiver<String>| async move {
+        while let Some(addr) = rx.next().await {
+            if let Some(destination) = Destination::parse(&addr) {
+                address_book.write().add_recent(destination);
+            }
+        }
+    });
+
+    let mut loaded = true;
+    use_effect(move || {
+        let book = address_book.read();
+        // 第一次运行只是订阅，内容和文件里的相同
+        if std::mem::take(&mut loaded) {
+            return;
+        }
+        if let Err(e) = address_book::save(&book) {
+            _ = log_tx
+                .read()
+                .unbounded_send(format!("{} : {e}", t!("address_book_fail")));
+        }
+    });
+
+    (address_book, Signal::new(recent_tx.tx()))
+}
+
+/// 接收端各连接的进度列表，收到某个对端的 `None` 时移除它
+fn use_transfers() -> (
+    Signal<UnboundedSender<ProgressUpdate>>,
+    Signal<Vec<Progress>>,
+) {
+    let mut transfers = use_signal(Vec::<Progress>::new);
+    let progress_tx = use_coroutine(
+        move |mut rx: UnboundedReceiver<ProgressUpdate>| async move {
+            while let Some((peer, progress)) = rx.next().await {
+                let mut transfers = transfers.write();
+                let index = transfers.iter().position(|p| p.peer == Some(peer));
+                match (index, progress) {
+                    (Some(index), Some(progress)) => transfers[index] = progress,
+                    (None, Some(progress)) => transfers.push(progress),
+                    (Some(index), None) => _ = transfers.remove(index),
+                    (None, None) => {}
+                }
+            }
+        },
+    );
+
+    (Signal::new(progress_tx.tx()), transfers)
+}
+
+/// 限速输入框及其对应的令牌桶，输入合法的值后立即生效
+fn use_rate_limit(initial: f64) -> (FormField<f64>, Signal<Arc<RateLimit>>) {
+    let field = use_form_field(initial, |s| {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(0.0);
+        }
+        s.parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .ok_or_else(|| t!("rate_limit_validation"))
+    });
+    let rate_limit = use_signal(|| Arc::new(RateLimit::default()));
+
+    use_effect(move || rate_limit.read().set_mb_per_sec(*field.value.read()));
+
+    (field, rate_limit)
+}
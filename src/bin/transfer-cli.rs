@@ -0,0 +1,393 @@
+//! 无界面的命令行版本，与桌面版使用同一套协议，可以互相传输
+use anyhow::Context;
+use rust_i18n::t;
+use std::{
+    collections::HashSet,
+    io::{BufRead, IsTerminal, Write},
+    path::PathBuf,
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        mpsc, Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+use transfer_dioxus::{
+    history,
+    receiver::{
+        advertise, handle_receive, receive_once, ConflictAction, ConflictPolicy, Decision,
+        EventSink, Prompt, ReceiveOptions, TransferEvent,
+    },
+    sender::{browse, handle_send, LinkMode, RateLimit, Target, TransferControl},
+    transfer_protocol::{
+        format_size,
+        progress::{format_duration, Progress},
+    },
+};
+
+const BAR_WIDTH: usize = 30;
+
+rust_i18n::i18n!("locales");
+
+fn main() -> ExitCode {
+    let lang = std::env::var("LANG").unwrap_or_default();
+    rust_i18n::set_locale(if lang.starts_with("zh") { "zh" } else { "en" });
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("send") => parse_send(&args[1..]).map(send),
+        Some("receive") => parse_receive(&args[1..]).map(receive),
+        Some("discover") => parse_discover(&args[1..]).map(discover),
+        Some("-h" | "--help") => {
+            println!("{}", t!("cli_usage"));
+            return ExitCode::SUCCESS;
+        }
+        Some(other) => Err(anyhow::anyhow!("{} : {other}", t!("unknown_command"))),
+        None => Err(anyhow::anyhow!(t!("missing_command"))),
+    };
+
+    match result {
+        Ok(Ok(())) => ExitCode::SUCCESS,
+        Ok(Err(e)) => {
+            eprintln!("{e:#}");
+            ExitCode::from(1)
+        }
+        Err(e) => {
+            eprintln!("{e:#}\n\n{}", t!("cli_usage"));
+            ExitCode::from(2)
+        }
+    }
+}
+
+struct SendArgs {
+    path: PathBuf,
+    addr: Target,
+    code: Option<String>,
+    limit: f64,
+    link_mode: LinkMode,
+}
+
+struct ReceiveArgs {
+    dir: PathBuf,
+    code: Option<String>,
+    port: u16,
+    limit: f64,
+    conflict_policy: ConflictPolicy,
+    yes: bool,
+    once: bool,
+    announce: bool,
+}
+
+fn parse_send(args: &[String]) -> anyhow::Result<SendArgs> {
+    let mut positional = Vec::new();
+    let mut code = None;
+    let mut limit = 0.0;
+    let mut link_mode = LinkMode::Preserve;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--code" => code = Some(value(&mut args, arg)?.to_string()),
+            "--limit" => limit = parse_limit(value(&mut args, arg)?)?,
+            "--follow-links" => link_mode = LinkMode::Follow,
+            flag if flag.starts_with("--") => anyhow::bail!("{} : {flag}", t!("unknown_option")),
+            _ => positional.push(arg),
+        }
+    }
+
+    let [path, addr] = positional[..] else {
+        anyhow::bail!(t!("send_needs_args"));
+    };
+    Ok(SendArgs {
+        path: path.into(),
+        addr: addr.parse::<Target>()?.with_default_port(8000),
+        code,
+        limit,
+        link_mode,
+    })
+}
+
+fn parse_receive(args: &[String]) -> anyhow::Result<ReceiveArgs> {
+    let mut parsed = ReceiveArgs {
+        dir: std::env::current_dir()?,
+        code: None,
+        port: 8000,
+        limit: 0.0,
+        conflict_policy: ConflictPolicy::default(),
+        yes: false,
+        once: false,
+        announce: true,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => parsed.dir = value(&mut args, arg)?.into(),
+            "--code" => parsed.code = Some(value(&mut args, arg)?.to_string()),
+            "--port" => {
+                parsed.port = value(&mut args, arg)?
+                    .parse()
+                    .with_context(|| t!("port_validation"))?
+            }
+            "--limit" => parsed.limit = parse_limit(value(&mut args, arg)?)?,
+            "--on-conflict" => parsed.conflict_policy = value(&mut args, arg)?.parse()?,
+            "--yes" => parsed.yes = true,
+            "--once" => parsed.once = true,
+            "--no-announce" => parsed.announce = false,
+            other => anyhow::bail!("{} : {other}", t!("unknown_option")),
+        }
+    }
+    Ok(parsed)
+}
+
+/// 返回监听通告的秒数
+fn parse_discover(args: &[String]) -> anyhow::Result<u64> {
+    let mut secs = 5;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--secs" => {
+                secs = value(&mut args, arg)?
+                    .parse()
+                    .with_context(|| format!("{} : {arg}", t!("needs_number")))?
+            }
+            other => anyhow::bail!("{} : {other}", t!("unknown_option")),
+        }
+    }
+    Ok(secs)
+}
+
+fn value<'a>(args: &mut impl Iterator<Item = &'a String>, flag: &str) -> anyhow::Result<&'a str> {
+    args.next()
+        .map(String::as_str)
+        .with_context(|| format!("{} : {flag}", t!("needs_value")))
+}
+
+fn parse_limit(s: &str) -> anyhow::Result<f64> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite() && *v >= 0.0)
+        .with_context(|| t!("rate_limit_validation"))
+}
+
+fn send(args: SendArgs) -> anyhow::Result<()> {
+    let code = match args.code.or_else(|| std::env::var("TRANSFER_CODE").ok()) {
+        Some(code) => code,
+        None => ask(&format!("{} : ", t!("pairing_code")))?,
+    };
+
+    let (events, printer) = spawn_printer(false);
+    let rate_limit = Arc::new(RateLimit::default());
+    rate_limit.set_mb_per_sec(args.limit);
+    let result = handle_send(
+        args.addr,
+        args.path,
+        &code,
+        events,
+        Arc::new(TransferControl::new(rate_limit)),
+        args.link_mode,
+    );
+
+    // 事件发送端全部丢弃后打印线程才会结束，保证日志都已输出
+    if let Ok(mut terminal) = printer.join() {
+        terminal.finish();
+    }
+    result
+}
+
+fn receive(args: ReceiveArgs) -> anyhow::Result<()> {
+    let (events, printer) = spawn_printer(args.yes);
+    let rate_limit = Arc::new(RateLimit::default());
+    rate_limit.set_mb_per_sec(args.limit);
+    // 指定配对码后整个会话都用它，脚本可以事先把同一个码交给两端
+    let pairing_code = args.code.or_else(|| std::env::var("TRANSFER_CODE").ok());
+    let options = ReceiveOptions {
+        rate_limit,
+        conflict_policy: args.conflict_policy,
+        pairing_code: pairing_code.filter(|code| !code.trim().is_empty()),
+    };
+    let addr = format!("0.0.0.0:{}", args.port);
+
+    let running = Arc::new(AtomicBool::new(true));
+    if args.announce {
+        let (port, running) = (args.port, running.clone());
+        std::thread::spawn(move || advertise(port, running));
+    }
+
+    if !args.once {
+        // 一直运行到进程被结束
+        return handle_receive(addr, args.dir, events, running, options);
+    }
+
+    let result = receive_once(addr, args.dir, events, options);
+    running.store(false, Relaxed);
+    let mut terminal = printer
+        .join()
+        .map_err(|_| anyhow::anyhow!(t!("printer_panicked")))?;
+    terminal.finish();
+    match result {
+        Ok(Some(save_path)) => {
+            terminal.log(&t!("receive_over"));
+            terminal.log(&format!("{} : {save_path:?}", t!("save_path")));
+            Ok(())
+        }
+        Ok(None) => anyhow::bail!(t!("rejected")),
+        Err(e) => anyhow::bail!("{} : {e}", t!("receive_fail")),
+    }
+}
+
+/// 在给定的秒数内列出局域网内发现的接收端
+fn discover(secs: u64) -> anyhow::Result<()> {
+    let running = Arc::new(AtomicBool::new(true));
+    let timer = running.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_secs(secs));
+        timer.store(false, Relaxed);
+    });
+
+    let mut seen = HashSet::new();
+    browse(running, |peers| {
+        for peer in peers {
+            if seen.insert(peer.addr) {
+                println!("{}\t{}\tv{}", peer.addr, peer.name, peer.version);
+            }
+        }
+    })
+}
+
+/// 在一个线程里依次处理所有事件：打印日志和进度条、回答提问，事件发送端全部丢弃后线程结束
+fn spawn_printer(yes: bool) -> (Arc<dyn EventSink>, JoinHandle<Terminal>) {
+    let (tx, rx) = mpsc::channel();
+    let printer = std::thread::spawn(move || {
+        let mut terminal = Terminal::new();
+        for event in rx {
+            match event {
+                TransferEvent::Progress(progress) => terminal.progress(&progress),
+                TransferEvent::PairingCode(code) => {
+                    terminal.log(&format!("{} : {code}", t!("pairing_code")))
+                }
+                TransferEvent::Prompt(prompt) => answer(prompt, yes),
+                TransferEvent::Record(record) => {
+                    if let Err(e) = history::append(&record) {
+                        terminal.log(&format!("{} : {e}", t!("history_fail")));
+                    }
+                }
+                event => {
+                    if let Some(line) = event.log_line() {
+                        terminal.log(&line);
+                    }
+                }
+            }
+        }
+        terminal
+    });
+    let events = Arc::new(move |event| _ = tx.send(event));
+    (events, printer)
+}
+
+/// 没有 `--yes` 时在终端上询问；标准输入不是终端时拒绝传输、保留已有文件，避免脚本卡住
+fn answer(prompt: Prompt, yes: bool) {
+    let interactive = std::io::stdin().is_terminal();
+    match prompt {
+        Prompt::Manifest(prompt) => {
+            let accept = yes
+                || interactive
+                    && ask(&format!(
+                        "{} : {} ({}) [y/N] ",
+                        t!("incoming"),
+                        prompt.manifest.top_level_names.join(", "),
+                        prompt.peer
+                    ))
+                    .is_ok_and(|a| a.eq_ignore_ascii_case("y"));
+            let decision = if accept {
+                Decision::Accept(prompt.save_path)
+            } else {
+                Decision::Reject
+            };
+            _ = prompt.reply.send(decision);
+        }
+        Prompt::Conflict(prompt) => {
+            let reply = if interactive {
+                ask(&format!(
+                    "{} : {:?} {} ",
+                    t!("file_exists"),
+                    prompt.path,
+                    t!("conflict_choices")
+                ))
+                .unwrap_or_default()
+            } else {
+                String::new()
+            };
+            let action = match reply.to_lowercase().as_str() {
+                "o" | "overwrite" => ConflictAction::Overwrite,
+                "r" | "rename" => ConflictAction::Rename,
+                _ => ConflictAction::Skip,
+            };
+            _ = prompt.reply.send(action);
+        }
+    }
+}
+
+fn ask(question: &str) -> anyhow::Result<String> {
+    eprint!("{question}");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+/// 日志和进度条都写到 stderr，打印日志后在下一行重画进度条；
+/// stderr 不是终端时（例如 CI 日志）不画进度条
+struct Terminal {
+    bar: String,
+    interactive: bool,
+}
+
+impl Terminal {
+    fn new() -> Self {
+        Self {
+            bar: String::new(),
+            interactive: std::io::stderr().is_terminal(),
+        }
+    }
+
+    fn log(&mut self, msg: &str) {
+        if self.interactive {
+            eprint!("\r\x1b[2K{msg}\n{}", self.bar);
+        } else {
+            eprintln!("{msg}");
+        }
+    }
+
+    fn progress(&mut self, progress: &Progress) {
+        if !self.interactive {
+            return;
+        }
+        let percent = progress.percent();
+        let eta = progress
+            .eta
+            .map(format_duration)
+            .unwrap_or_else(|| "--:--".to_string());
+        let filled = ((percent / 100.0) * BAR_WIDTH as f64).round() as usize;
+        let filled = filled.min(BAR_WIDTH);
+        self.bar = format!(
+            "[{}{}] {percent:>5.1}% {}/{} {}/{} {}/s {eta}",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            format_size(progress.bytes_done as f64),
+            format_size(progress.bytes_total as f64),
+            progress.files_done,
+            progress.files_total,
+            format_size(progress.current_speed)
+        );
+        eprint!("\r\x1b[2K{}", self.bar);
+    }
+
+    fn finish(&mut self) {
+        if !self.bar.is_empty() {
+            eprintln!();
+            self.bar.clear();
+        }
+    }
+}
@@ -2,14 +2,21 @@ use std::fmt::Display;
 
 use dioxus::prelude::*;
 
-#[derive(Clone, Copy)]
-pub struct FormField<T> {
+pub struct FormField<T: 'static> {
     pub value: Signal<T>,
     pub raw_value: Signal<String>,
     pub error: Signal<Option<String>>,
     pub oninput: EventHandler<FormEvent>,
     pub mounted: Signal<Option<MountedEvent>>,
 }
+// 手动实现，值的类型不需要是 `Copy`
+impl<T> Clone for FormField<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for FormField<T> {}
+
 impl<T> FormField<T> {
     pub async fn focus(&self) {
         if let Some(mounted) = &*self.mounted.read() {
@@ -17,6 +24,14 @@ impl<T> FormField<T> {
         }
     }
 }
+impl<T: ToString + 'static> FormField<T> {
+    /// 从代码里填入一个合法的值，同时更新输入框的文字
+    pub fn set(&mut self, value: T) {
+        self.raw_value.set(value.to_string());
+        self.value.set(value);
+        self.error.set(None);
+    }
+}
 
 pub fn use_form_field<T, E>(
     initial_value: T,
@@ -0,0 +1,50 @@
+//! 传输历史，每条记录一行 JSON，追加写在数据目录下
+pub use crate::transfer_protocol::record::{Direction, Outcome, TransferRecord};
+use std::{
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::PathBuf,
+};
+
+pub fn history_path() -> Option<PathBuf> {
+    Some(
+        dirs::data_dir()?
+            .join("transfer-dioxus")
+            .join("history.jsonl"),
+    )
+}
+
+pub fn append(record: &TransferRecord) -> anyhow::Result<()> {
+    let path = history_path().ok_or_else(|| anyhow::anyhow!("找不到数据目录"))?;
+    fs::create_dir_all(path.parent().unwrap_or(&path))?;
+
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    // 一次写入整行，多个连接同时结束时也不会交错
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(&line)?;
+    Ok(())
+}
+
+/// 按时间从新到旧返回全部记录，跳过无法解析的行
+pub fn load() -> anyhow::Result<Vec<TransferRecord>> {
+    let Some(path) = history_path() else {
+        return Ok(Vec::new());
+    };
+    let file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut records: Vec<TransferRecord> = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect();
+    records.reverse();
+    Ok(records)
+}
@@ -1,9 +1,17 @@
+#[cfg(feature = "ui")]
+pub mod address_book;
+#[cfg(feature = "ui")]
 pub mod app_state;
+pub mod history;
 pub mod receiver;
 pub mod sender;
+#[cfg(feature = "ui")]
+pub mod settings;
+pub mod transfer_protocol;
+#[cfg(feature = "ui")]
 pub mod ui;
 
+#[cfg(feature = "ui")]
 mod form_field;
-mod transfer_protocol;
 
 rust_i18n::i18n!("locales");
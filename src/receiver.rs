@@ -1,59 +1,58 @@
-use crate::transfer_protocol::receive_protocol::ReceiveProtocol;
+pub use crate::transfer_protocol::{
+    conflict::{ConflictAction, ConflictPolicy, ConflictPrompt},
+    control::RateLimit,
+    discovery::advertise,
+    event::{EventSink, Prompt, TransferEvent},
+    manifest::{Decision, Manifest, ManifestPrompt},
+    receive_protocol::ReceiveOptions,
+};
+use crate::transfer_protocol::{
+    format_size,
+    pairing::PairingCode,
+    receive_protocol::ReceiveProtocol,
+    record::{Direction, Outcome, TransferRecord},
+    secure_stream::local_fingerprint,
+    temp_file,
+};
 use anyhow::Context;
-use dioxus::hooks::UnboundedSender;
 use rust_i18n::t;
 use std::io::ErrorKind;
 use std::sync::atomic::Ordering::Relaxed;
 use std::{
     fs::create_dir_all,
-    net::{TcpListener, ToSocketAddrs},
-    path::PathBuf,
-    sync::{atomic::AtomicBool, Arc},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, mpsc, Arc},
+    time::Instant,
 };
 
 pub fn handle_receive(
     addr: impl ToSocketAddrs,
     save_path: PathBuf,
-    log_tx: UnboundedSender<String>,
+    events: Arc<dyn EventSink>,
     running: Arc<AtomicBool>,
+    options: ReceiveOptions,
 ) -> anyhow::Result<()> {
-    create_dir_all(&save_path)?;
-    log_tx.unbounded_send(format!("{} : {save_path:?}", t!("save_path")))?;
-
-    let listener = TcpListener::bind(addr).with_context(|| t!("change_port"))?;
-    log_tx.unbounded_send(format!(
-        "{} : {}",
-        t!("start_server_success"),
-        listener.local_addr()?
-    ))?;
-
+    let listener = listen(addr, &save_path, &*events)?;
     listener
         .set_nonblocking(true)
         .with_context(|| "设置非阻塞模式失败")?;
 
+    let pairing_code = pairing_code(&options, &events);
+
     while running.load(Relaxed) {
         match listener.accept() {
             Ok((stream, a)) => {
-                log_tx.unbounded_send(format!("{} : {}", t!("new_connection"), a))?;
+                events.log(format!("{} : {}", t!("new_connection"), a));
 
                 let save_path = save_path.clone();
-                let log_thread = log_tx.clone();
+                let events = events.clone();
+                let pairing_code = pairing_code.clone();
+                let options = options.clone();
                 std::thread::spawn(move || {
-                    let mut stream = ReceiveProtocol::new(stream);
-                    match stream.receive_file_or_dir(&save_path, &log_thread) {
-                        Ok(_) => {
-                            _ = log_thread.unbounded_send(t!("receive_over").to_string());
-                            _ = log_thread
-                                .unbounded_send(format!("{} : {save_path:?}", t!("save_path")));
-                        }
-                        Err(e) => {
-                            _ = log_thread.unbounded_send(format!(
-                                "{} : {}",
-                                t!("receive_fail"),
-                                e
-                            ));
-                        }
-                    };
+                    let result =
+                        handle_connection(stream, a, save_path, &pairing_code, &options, &events);
+                    report(result, a, &*events);
                 });
             }
             Err(e) if e.kind() == ErrorKind::WouldBlock => {
@@ -66,3 +65,172 @@ pub fn handle_receive(
     }
     Ok(())
 }
+
+/// 只接受一个连接并在它结束后返回，返回值同 `handle_connection`
+pub fn receive_once(
+    addr: impl ToSocketAddrs,
+    save_path: PathBuf,
+    events: Arc<dyn EventSink>,
+    options: ReceiveOptions,
+) -> anyhow::Result<Option<PathBuf>> {
+    let listener = listen(addr, &save_path, &*events)?;
+    let pairing_code = pairing_code(&options, &events);
+
+    let (stream, peer) = listener.accept()?;
+    events.log(format!("{} : {peer}", t!("new_connection")));
+    handle_connection(stream, peer, save_path, &pairing_code, &options, &events)
+}
+
+/// 选项里指定了配对码时整个会话都用它
+fn pairing_code(options: &ReceiveOptions, events: &Arc<dyn EventSink>) -> PairingCode {
+    match &options.pairing_code {
+        Some(code) => PairingCode::fixed(code.clone(), events.clone()),
+        None => PairingCode::new(events.clone()),
+    }
+}
+
+/// 把一个连接的结果告诉调用方
+fn report(result: anyhow::Result<Option<PathBuf>>, peer: SocketAddr, events: &dyn EventSink) {
+    match result {
+        Ok(Some(save_path)) => {
+            events.log(t!("receive_over").to_string());
+            events.log(format!("{} : {save_path:?}", t!("save_path")));
+        }
+        Ok(None) => events.log(format!("{} : {peer}", t!("rejected"))),
+        Err(e) => events.emit(TransferEvent::Error(format!(
+            "{} : {e}",
+            t!("receive_fail")
+        ))),
+    }
+    events.emit(TransferEvent::Closed { peer });
+}
+
+/// 准备保存目录并开始监听
+fn listen(
+    addr: impl ToSocketAddrs,
+    save_path: &Path,
+    events: &dyn EventSink,
+) -> anyhow::Result<TcpListener> {
+    create_dir_all(save_path)?;
+    events.log(format!("{} : {save_path:?}", t!("save_path")));
+
+    let removed = temp_file::remove_stale(save_path);
+    if removed > 0 {
+        events.log(format!("{} : {removed}", t!("removed_temp_files")));
+    }
+
+    let listener = TcpListener::bind(addr).with_context(|| t!("change_port"))?;
+    events.log(format!(
+        "{} : {}",
+        t!("start_server_success"),
+        listener.local_addr()?
+    ));
+    events.log(format!(
+        "{} : {}",
+        t!("local_fingerprint"),
+        local_fingerprint()
+    ));
+    Ok(listener)
+}
+
+/// 处理单个连接，返回实际使用的保存路径，用户拒绝时返回 `None`；结束后发出这个连接的记录
+fn handle_connection(
+    stream: TcpStream,
+    peer: SocketAddr,
+    save_path: PathBuf,
+    pairing_code: &PairingCode,
+    options: &ReceiveOptions,
+    events: &Arc<dyn EventSink>,
+) -> anyhow::Result<Option<PathBuf>> {
+    let mut record = TransferRecord::new(Direction::Receive, peer.to_string(), save_path.clone());
+    let started = Instant::now();
+    let result = receive_connection(
+        stream,
+        peer,
+        save_path,
+        pairing_code,
+        options,
+        events,
+        &mut record,
+    );
+
+    let outcome = match &result {
+        Ok(Some(_)) => Outcome::Completed,
+        Ok(None) => Outcome::Rejected,
+        Err(_) => Outcome::Failed,
+    };
+    record.finish(outcome, result.as_ref().err(), started.elapsed());
+    events.emit(TransferEvent::Record(record));
+    result
+}
+
+/// 接收并把清单信息和实际的保存路径填进 `record`
+fn receive_connection(
+    stream: TcpStream,
+    peer: SocketAddr,
+    save_path: PathBuf,
+    pairing_code: &PairingCode,
+    options: &ReceiveOptions,
+    events: &Arc<dyn EventSink>,
+    record: &mut TransferRecord,
+) -> anyhow::Result<Option<PathBuf>> {
+    stream.set_nonblocking(false)?;
+    let mut stream = ReceiveProtocol::new(stream, pairing_code, options, events.clone())?;
+    events.emit(TransferEvent::Started {
+        peer,
+        fingerprint: stream.peer_fingerprint().to_string(),
+    });
+
+    let save_path = match stream.receive_manifest()? {
+        Some(manifest) => {
+            events.log(format!(
+                "{} : {} ({}, {} {}, {})",
+                t!("incoming"),
+                manifest.top_level_names.join(", "),
+                manifest.hostname,
+                manifest.file_count,
+                t!("files"),
+                format_size(manifest.total_size as f64)
+            ));
+            record.peer_name = manifest.hostname.clone();
+            record.names = manifest.top_level_names.clone();
+            record.files = manifest.file_count;
+            record.bytes = manifest.total_size;
+
+            let (reply, decision) = mpsc::channel();
+            events.emit(TransferEvent::Prompt(Prompt::Manifest(ManifestPrompt {
+                peer,
+                manifest,
+                save_path,
+                reply,
+            })));
+            // 界面关闭了弹窗却没有回复时按拒绝处理
+            let decision = decision.recv().unwrap_or(Decision::Reject);
+            stream.reply_manifest(&decision)?;
+
+            match decision {
+                Decision::Accept(save_path) => {
+                    create_dir_all(&save_path)?;
+                    record.local_path = save_path.clone();
+                    save_path
+                }
+                Decision::Reject => return Ok(None),
+            }
+        }
+        None => save_path,
+    };
+
+    stream.receive_file_or_dir(&save_path, &**events, |path, size, existing_size| {
+        let (reply, action) = mpsc::channel();
+        events.emit(TransferEvent::Prompt(Prompt::Conflict(ConflictPrompt {
+            peer,
+            path: path.to_path_buf(),
+            size,
+            existing_size,
+            reply,
+        })));
+        // 调用方丢弃了提问却没有回复时保留已有文件
+        action.recv().unwrap_or(ConflictAction::Skip)
+    })?;
+    Ok(Some(save_path))
+}
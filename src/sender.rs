@@ -1,59 +1,185 @@
-use crate::transfer_protocol::send_protocol::SendProtocol;
+pub use crate::transfer_protocol::{
+    control::{RateLimit, TransferControl},
+    discovery::{browse, Peer},
+    event::{EventSink, TransferEvent},
+    target::Target,
+};
+use crate::transfer_protocol::{
+    links::HardLinks,
+    manifest::Manifest,
+    record::{Direction, Outcome, TransferRecord},
+    secure_stream::local_fingerprint,
+    send_protocol::SendProtocol,
+};
 use anyhow::Context;
-use dioxus::hooks::UnboundedSender;
 use rust_i18n::t;
 use std::{
     fmt::Debug,
-    net::{TcpStream, ToSocketAddrs},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
     path::PathBuf,
-    time::Duration,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 use walkdir::WalkDir;
 
+/// 遇到符号链接时的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LinkMode {
+    /// 符号链接和硬链接按链接发送，由接收端重建
+    #[default]
+    Preserve,
+    /// 发送链接指向的内容
+    Follow,
+}
+
 pub fn handle_send(
     addr: impl ToSocketAddrs + Debug,
     send_path: PathBuf,
-    log_tx: UnboundedSender<String>,
-    progress_tx: UnboundedSender<(f64, String)>,
+    pairing_code: &str,
+    events: Arc<dyn EventSink>,
+    control: Arc<TransferControl>,
+    link_mode: LinkMode,
 ) -> anyhow::Result<()> {
+    let mut record = TransferRecord::new(Direction::Send, format!("{addr:?}"), send_path.clone());
+    let started = Instant::now();
+    let result = send(
+        addr,
+        send_path,
+        pairing_code,
+        &events,
+        control.clone(),
+        link_mode,
+        &mut record,
+    );
+
+    let outcome = match &result {
+        Ok(outcome) => *outcome,
+        Err(_) if control.is_cancelled() => Outcome::Cancelled,
+        Err(_) => Outcome::Failed,
+    };
+    record.finish(outcome, result.as_ref().err(), started.elapsed());
+    events.emit(TransferEvent::Record(record));
+
+    if result? == Outcome::Rejected {
+        anyhow::bail!(t!("rejected"));
+    }
+    Ok(())
+}
+
+/// 发送并把对端和清单信息填进 `record`，接收端拒绝时返回 `Outcome::Rejected`
+fn send(
+    addr: impl ToSocketAddrs + Debug,
+    send_path: PathBuf,
+    pairing_code: &str,
+    events: &Arc<dyn EventSink>,
+    control: Arc<TransferControl>,
+    link_mode: LinkMode,
+    record: &mut TransferRecord,
+) -> anyhow::Result<Outcome> {
     if !send_path.exists() {
         anyhow::bail!(t!("no_selected_file"));
     }
 
-    let total_size = WalkDir::new(&send_path)
+    let entries: Vec<_> = WalkDir::new(&send_path)
+        .follow_links(link_mode == LinkMode::Follow)
         .into_iter()
         .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_file()) // 只计算文件
-        .map(|e| e.metadata().map(|m| m.len()).unwrap_or(0))
-        .sum::<u64>();
+        .collect();
 
-    let mut addr = addr
+    // 只计算文件，同一文件的多个硬链接只计一次
+    let mut hard_links = HardLinks::default();
+    let (file_count, total_size) = entries
+        .iter()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok().map(|m| (e, m)))
+        .filter(|(e, m)| hard_links.check(m, e.path()).is_none())
+        .fold((0, 0), |(count, size), (_, m)| (count + 1, size + m.len()));
+
+    let addrs: Vec<_> = addr
         .to_socket_addrs()
-        .with_context(|| format!("{} : {addr:?}", t!("invalid_ip")))?;
-    let socket_addr = addr.next().context("没有ip地址")?;
-    let stream = TcpStream::connect_timeout(&socket_addr, Duration::from_secs(3))?;
-    log_tx.unbounded_send(t!("connected").to_string())?;
+        .with_context(|| format!("{} : {addr:?}", t!("resolve_fail")))?
+        .collect();
+    let (stream, socket_addr) = connect_any(&addrs, events)?;
+    record.peer = socket_addr.to_string();
+    events.log(t!("connected").to_string());
 
     let root_dir = send_path.parent().context("发送路径是根目录或空")?;
-    let paths = WalkDir::new(&send_path);
 
-    let mut stream = SendProtocol::new(stream, total_size, progress_tx);
+    let mut stream = SendProtocol::new(
+        stream,
+        pairing_code,
+        file_count,
+        total_size,
+        events.clone(),
+        control,
+    )?;
+    events.log(format!(
+        "{} : {}",
+        t!("local_fingerprint"),
+        local_fingerprint()
+    ));
+    events.emit(TransferEvent::Started {
+        peer: socket_addr,
+        fingerprint: stream.peer_fingerprint().to_string(),
+    });
 
-    for entry in paths {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            stream.send_file_or_dir(path, root_dir, &log_tx)?;
-        }
+    let manifest = Manifest {
+        file_count,
+        total_size,
+        top_level_names: send_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .into_iter()
+            .collect(),
+        hostname: hostname::get()
+            .map(|h| h.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    record.names = manifest.top_level_names.clone();
+    record.files = file_count;
+    record.bytes = total_size;
+    events.log(t!("waiting_accept").to_string());
+    if !stream.send_manifest(&manifest)? {
+        return Ok(Outcome::Rejected);
+    }
+
+    for entry in &entries {
+        stream.send_file_or_dir(entry, root_dir)?;
     }
 
-    stream.flush()?;
-    stream.get_ref().send_process();
+    let summary = stream.finish()?;
+    stream.get_mut().send_process();
 
-    log_tx.unbounded_send(format!(
-        "{} : {:?}",
-        t!("time_taken"),
-        stream.get_ref().total_time()
-    ))?;
+    events.emit(TransferEvent::Finished {
+        summary,
+        elapsed: stream.get_ref().total_time(),
+    });
+    if let Some(summary) = summary.filter(|s| s.failed > 0) {
+        anyhow::bail!("{} : {}", t!("verify_fail_count"), summary.failed);
+    }
 
-    Ok(())
+    Ok(Outcome::Completed)
+}
+
+/// 依次尝试解析出的每个地址（主机名常常同时有 IPv6 和 IPv4 地址），全部失败时返回最后一个错误
+fn connect_any(
+    addrs: &[SocketAddr],
+    events: &Arc<dyn EventSink>,
+) -> anyhow::Result<(TcpStream, SocketAddr)> {
+    let mut last_error = None;
+    for addr in addrs {
+        match TcpStream::connect_timeout(addr, Duration::from_secs(3)) {
+            Ok(stream) => return Ok((stream, *addr)),
+            Err(e) => {
+                if addrs.len() > 1 {
+                    events.log(format!("{} : {addr} : {e}", t!("connect_fail")));
+                }
+                last_error = Some(e);
+            }
+        }
+    }
+    match last_error {
+        Some(e) => Err(e.into()),
+        None => anyhow::bail!("没有ip地址"),
+    }
 }
@@ -0,0 +1,68 @@
+//! 桌面端的设置，保存在配置目录下，启动时读取
+use crate::{app_state::Language, receiver::ConflictPolicy};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub sender_port: u16,
+    pub receiver_port: u16,
+    /// 接收文件的保存目录
+    pub save_dir: PathBuf,
+    /// daisyUI 主题名
+    pub theme: String,
+    pub language: Language,
+    /// 发送和接收限速，单位 MB/s，0 表示不限速
+    pub send_limit: f64,
+    pub receive_limit: f64,
+    /// 接收端保存路径上已有同名文件时的处理方式
+    pub conflict_policy: ConflictPolicy,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            sender_port: 8000,
+            receiver_port: 8000,
+            save_dir: std::fs::canonicalize(".").unwrap_or_default(),
+            theme: "light".to_string(),
+            language: Language::default(),
+            send_limit: 0.0,
+            receive_limit: 0.0,
+            conflict_policy: ConflictPolicy::default(),
+        }
+    }
+}
+
+pub fn settings_path() -> Option<PathBuf> {
+    Some(
+        dirs::config_dir()?
+            .join("transfer-dioxus")
+            .join("settings.json"),
+    )
+}
+
+/// 文件不存在或无法解析时使用默认值，缺少的字段也取默认值
+pub fn load() -> Settings {
+    let mut settings: Settings = settings_path()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default();
+    // 保存目录被删除或移走后回到默认目录
+    if !settings.save_dir.is_dir() {
+        settings.save_dir = Settings::default().save_dir;
+    }
+    settings
+}
+
+pub fn save(settings: &Settings) -> anyhow::Result<()> {
+    let path = settings_path().ok_or_else(|| anyhow::anyhow!("找不到配置目录"))?;
+    fs::create_dir_all(path.parent().unwrap_or(&path))?;
+
+    // 先写临时文件再替换，写到一半退出也不会留下损坏的设置
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(settings)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
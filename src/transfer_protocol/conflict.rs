@@ -0,0 +1,110 @@
+use crate::transfer_protocol::sanitize::numbered;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc::Sender,
+};
+
+/// 保存路径上已有同名文件时的处理方式，保存到设置里的名字和 `as_str` 一致
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// 覆盖，上次中断留下了临时文件时从断点继续
+    #[default]
+    Overwrite,
+    Skip,
+    /// 大小和摘要都相同时跳过，否则覆盖
+    #[serde(rename = "skip_identical")]
+    SkipIfIdentical,
+    /// 另存为 "name (1).ext"
+    Rename,
+    /// 每个文件都询问用户
+    Ask,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [ConflictPolicy; 5] = [
+        ConflictPolicy::Overwrite,
+        ConflictPolicy::Skip,
+        ConflictPolicy::SkipIfIdentical,
+        ConflictPolicy::Rename,
+        ConflictPolicy::Ask,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::SkipIfIdentical => "skip_identical",
+            ConflictPolicy::Rename => "rename",
+            ConflictPolicy::Ask => "ask",
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("未知的冲突策略 : {s}"))
+    }
+}
+
+/// 用户对单个冲突文件的回答
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictAction {
+    Overwrite,
+    Skip,
+    Rename,
+}
+
+impl From<ConflictAction> for ConflictPolicy {
+    fn from(action: ConflictAction) -> Self {
+        match action {
+            ConflictAction::Overwrite => ConflictPolicy::Overwrite,
+            ConflictAction::Skip => ConflictPolicy::Skip,
+            ConflictAction::Rename => ConflictPolicy::Rename,
+        }
+    }
+}
+
+/// 交给界面弹窗的冲突询问，用户的回答通过 `reply` 传回接收线程
+pub struct ConflictPrompt {
+    pub peer: SocketAddr,
+    /// 对端发来的相对路径
+    pub path: PathBuf,
+    pub size: u64,
+    pub existing_size: u64,
+    pub reply: Sender<ConflictAction>,
+}
+
+/// 在同一目录下找一个还不存在的 "name (n).ext"
+pub fn free_name(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    let name = path.file_name().unwrap_or_default();
+    (1..)
+        .map(|n| parent.join(numbered(name, n)))
+        .find(|candidate| candidate.symlink_metadata().is_err())
+        .expect("编号用尽")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 设置文件和命令行使用同一套名字
+    #[test]
+    fn serde_names_match_as_str() {
+        for policy in ConflictPolicy::ALL {
+            let json = serde_json::to_string(&policy).unwrap();
+            assert_eq!(json, format!("\"{}\"", policy.as_str()));
+            assert_eq!(serde_json::from_str::<ConflictPolicy>(&json).unwrap(), policy);
+            assert_eq!(policy.as_str().parse::<ConflictPolicy>().unwrap(), policy);
+        }
+    }
+}
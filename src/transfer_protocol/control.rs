@@ -0,0 +1,151 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// 令牌桶最多积攒这么长时间的额度，避免空闲后瞬间突发
+const BURST: Duration = Duration::from_millis(250);
+/// 等待令牌时单次睡眠的上限，让界面修改的速率尽快生效
+const MAX_WAIT: Duration = Duration::from_millis(100);
+/// 不为 0 的限速最低值，再低一个数据块就要发送很久，接收端会误以为连接已断开
+pub const MIN_BYTES_PER_SEC: u64 = 1024;
+
+/// 界面与发送线程共享的控制状态，发送线程在每个数据块之间检查
+#[derive(Debug, Default)]
+pub struct TransferControl {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+    rate_limit: Arc<RateLimit>,
+}
+
+impl TransferControl {
+    pub fn new(rate_limit: Arc<RateLimit>) -> Self {
+        Self {
+            rate_limit,
+            ..Default::default()
+        }
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Relaxed)
+    }
+    pub fn pause(&self) {
+        self.paused.store(true, Relaxed);
+    }
+    pub fn resume(&self) {
+        self.paused.store(false, Relaxed);
+    }
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Relaxed)
+    }
+    pub fn rate_limit(&self) -> &RateLimit {
+        &self.rate_limit
+    }
+}
+
+/// 多个传输共享的令牌桶限速，速率为 0 时不限速，修改后对进行中的传输立即生效
+#[derive(Debug)]
+pub struct RateLimit {
+    bytes_per_sec: AtomicU64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            bytes_per_sec: AtomicU64::new(0),
+            bucket: Mutex::new(Bucket {
+                tokens: 0.0,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl RateLimit {
+    /// 0 表示不限速，其他值不低于 `MIN_BYTES_PER_SEC`
+    pub fn set_mb_per_sec(&self, mb_per_sec: f64) {
+        let bytes_per_sec = match (mb_per_sec * 1024.0 * 1024.0) as u64 {
+            0 => 0,
+            n => n.max(MIN_BYTES_PER_SEC),
+        };
+        self.bytes_per_sec.store(bytes_per_sec, Relaxed);
+    }
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec.load(Relaxed)
+    }
+
+    /// 一次最多取这么多额度，按当前速率大约一秒能发完，低速时数据块随之变小
+    pub fn slice_len(&self, max: usize) -> usize {
+        match self.bytes_per_sec() {
+            0 => max,
+            rate => (rate as usize).clamp(1, max),
+        }
+    }
+
+    /// 取走 `n` 字节的额度，额度不足时阻塞到补足为止
+    pub fn consume(&self, n: usize) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill(self.bytes_per_sec());
+        bucket.tokens -= n as f64;
+
+        while bucket.tokens < 0.0 {
+            let rate = self.bytes_per_sec();
+            if rate == 0 {
+                bucket.tokens = 0.0;
+                return;
+            }
+
+            let wait = Duration::from_secs_f64(-bucket.tokens / rate as f64);
+            std::thread::sleep(wait.min(MAX_WAIT));
+            bucket.refill(rate);
+        }
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, rate: u64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        let capacity = rate as f64 * BURST.as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(capacity);
+        self.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_limits_are_clamped() {
+        let limit = RateLimit::default();
+        limit.set_mb_per_sec(0.0);
+        assert_eq!(limit.bytes_per_sec(), 0);
+        limit.set_mb_per_sec(0.0001);
+        assert_eq!(limit.bytes_per_sec(), MIN_BYTES_PER_SEC);
+        limit.set_mb_per_sec(1.0);
+        assert_eq!(limit.bytes_per_sec(), 1024 * 1024);
+    }
+
+    #[test]
+    fn slices_shrink_with_the_rate() {
+        let limit = RateLimit::default();
+        assert_eq!(limit.slice_len(64 * 1024), 64 * 1024);
+        limit.set_mb_per_sec(0.0001);
+        assert_eq!(limit.slice_len(64 * 1024), MIN_BYTES_PER_SEC as usize);
+        limit.set_mb_per_sec(10.0);
+        assert_eq!(limit.slice_len(64 * 1024), 64 * 1024);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::transfer_protocol::handshake::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const MAGIC: [u8; 4] = *b"TFDA";
+/// 本地网络内的组播地址，只在局域网内转发
+pub const DISCOVERY_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 70, 68);
+pub const DISCOVERY_PORT: u16 = 48_000;
+
+const KIND_ANNOUNCE: u8 = 0;
+/// 接收端停止服务时发出，发送端立即移除它
+const KIND_BYE: u8 = 1;
+
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+/// 连续几次收不到通告就认为对端已下线
+const PEER_TIMEOUT: Duration = Duration::from_secs(7);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 局域网内正在提供接收服务的设备
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Peer {
+    pub name: String,
+    /// 通告来源的 IP 和接收端监听的端口
+    pub addr: SocketAddr,
+    pub version: u16,
+}
+
+impl Peer {
+    /// 对端的协议版本过低时无法互传，界面上仍然列出但不可选
+    pub fn is_compatible(&self) -> bool {
+        self.version >= MIN_PROTOCOL_VERSION
+    }
+}
+
+/// 接收服务运行期间定时在局域网内通告本机的名字和端口，`running` 变为 false 后发出下线通知并返回
+pub fn advertise(port: u16, running: Arc<AtomicBool>) -> anyhow::Result<()> {
+    advertise_on(Ipv4Addr::UNSPECIFIED, port, running)
+}
+
+/// 从 `interface` 发出通告，未指定时由系统按路由选择
+fn advertise_on(interface: Ipv4Addr, port: u16, running: Arc<AtomicBool>) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_multicast_loop_v4(true)?;
+    if !interface.is_unspecified() {
+        socket2::SockRef::from(&socket).set_multicast_if_v4(&interface)?;
+    }
+    let group = SocketAddrV4::new(DISCOVERY_GROUP, DISCOVERY_PORT);
+    let name = hostname::get()
+        .map(|h| h.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let announce = encode(KIND_ANNOUNCE, port, &name);
+    let mut last_sent = None::<Instant>;
+    while running.load(Relaxed) {
+        if last_sent.is_none_or(|t| t.elapsed() >= ANNOUNCE_INTERVAL) {
+            // 暂时没有可用网络时下一轮再试
+            _ = socket.send_to(&announce, group);
+            last_sent = Some(Instant::now());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    socket.send_to(&encode(KIND_BYE, port, &name), group)?;
+    Ok(())
+}
+
+/// 监听局域网内的通告，对端列表有变化时以按名字排序的完整列表调用 `on_change`，`running` 变为 false 后返回
+pub fn browse(running: Arc<AtomicBool>, on_change: impl FnMut(Vec<Peer>)) -> anyhow::Result<()> {
+    browse_on(Ipv4Addr::UNSPECIFIED, running, on_change)
+}
+
+/// 只接收 `interface` 上的通告，未指定时由系统选择
+fn browse_on(
+    interface: Ipv4Addr,
+    running: Arc<AtomicBool>,
+    mut on_change: impl FnMut(Vec<Peer>),
+) -> anyhow::Result<()> {
+    let socket = bind_group(interface)?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let mut peers = HashMap::<SocketAddr, (Peer, Instant)>::new();
+    let mut buf = [0; 512];
+    while running.load(Relaxed) {
+        let mut changed = false;
+        if let Ok((n, from)) = socket.recv_from(&mut buf) {
+            if let Some((kind, peer)) = decode(&buf[..n], from) {
+                changed = match kind {
+                    KIND_BYE => peers.remove(&peer.addr).is_some(),
+                    _ => peers
+                        .insert(peer.addr, (peer.clone(), Instant::now()))
+                        .is_none_or(|(old, _)| old != peer),
+                };
+            }
+        }
+
+        let before = peers.len();
+        peers.retain(|_, (_, seen)| seen.elapsed() < PEER_TIMEOUT);
+        changed |= peers.len() != before;
+
+        if changed {
+            let mut list: Vec<Peer> = peers.values().map(|(peer, _)| peer.clone()).collect();
+            list.sort_by(|a, b| a.name.cmp(&b.name).then(a.addr.cmp(&b.addr)));
+            on_change(list);
+        }
+    }
+    Ok(())
+}
+
+/// 允许同一台机器上的多个实例同时监听通告
+fn bind_group(interface: Ipv4Addr) -> anyhow::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT).into())?;
+    socket.join_multicast_v4(&DISCOVERY_GROUP, &interface)?;
+    Ok(socket.into())
+}
+
+/// 魔数、类型、协议版本、端口和设备名
+fn encode(kind: u8, port: u16, name: &str) -> Vec<u8> {
+    let mut len = name.len().min(u8::MAX as usize);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    let mut packet = Vec::with_capacity(10 + len);
+    packet.extend_from_slice(&MAGIC);
+    packet.push(kind);
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    packet.extend_from_slice(&port.to_be_bytes());
+    packet.push(len as u8);
+    packet.extend_from_slice(&name.as_bytes()[..len]);
+    packet
+}
+
+/// 不认识的数据包返回 `None`
+fn decode(packet: &[u8], from: SocketAddr) -> Option<(u8, Peer)> {
+    let (header, name) = packet.split_at_checked(10)?;
+    if header[0..4] != MAGIC {
+        return None;
+    }
+    let kind = header[4];
+    let version = u16::from_be_bytes([header[5], header[6]]);
+    let port = u16::from_be_bytes([header[7], header[8]]);
+    let name = name.get(..header[9] as usize)?;
+
+    Some((
+        kind,
+        Peer {
+            name: String::from_utf8_lossy(name).into_owned(),
+            addr: SocketAddr::new(from.ip(), port),
+            version,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    /// 两个实例都走回环接口，不依赖本机的局域网
+    #[test]
+    fn browse_sees_advertise_on_loopback() {
+        // 避开同一台机器上可能正在运行的接收端
+        const PORT: u16 = 47_913;
+
+        let browsing = Arc::new(AtomicBool::new(true));
+        let (tx, rx) = mpsc::channel();
+        let browser = std::thread::spawn({
+            let browsing = browsing.clone();
+            move || browse_on(Ipv4Addr::LOCALHOST, browsing, |list| _ = tx.send(list))
+        });
+        // 等浏览端加入组播组后再开始通告
+        std::thread::sleep(POLL_INTERVAL);
+
+        let advertising = Arc::new(AtomicBool::new(true));
+        let advertiser = std::thread::spawn({
+            let advertising = advertising.clone();
+            move || advertise_on(Ipv4Addr::LOCALHOST, PORT, advertising)
+        });
+
+        let find = |list: &[Peer]| list.iter().find(|p| p.addr.port() == PORT).cloned();
+        let deadline = Instant::now() + PEER_TIMEOUT;
+        let peer = loop {
+            let list = rx
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .expect("没有收到通告");
+            if let Some(peer) = find(&list) {
+                break peer;
+            }
+        };
+        let name = hostname::get().unwrap().to_string_lossy().into_owned();
+        assert_eq!(peer.name, name);
+        assert_eq!(peer.addr, SocketAddr::from((Ipv4Addr::LOCALHOST, PORT)));
+        assert_eq!(peer.version, PROTOCOL_VERSION);
+        assert!(peer.is_compatible());
+
+        // 停止通告后发出的下线通知让浏览端立即移除它
+        advertising.store(false, Relaxed);
+        advertiser.join().unwrap().unwrap();
+        let removed_at = Instant::now();
+        loop {
+            let list = rx
+                .recv_timeout(POLL_INTERVAL * 4)
+                .expect("没有收到下线通知");
+            if find(&list).is_none() {
+                break;
+            }
+        }
+        assert!(removed_at.elapsed() < PEER_TIMEOUT);
+
+        browsing.store(false, Relaxed);
+        browser.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn decode_ignores_foreign_packets() {
+        let from = SocketAddr::from((Ipv4Addr::LOCALHOST, 1));
+        assert!(decode(b"hello world", from).is_none());
+        assert!(decode(&encode(KIND_ANNOUNCE, 8000, "pc")[..9], from).is_none());
+
+        let (kind, peer) = decode(&encode(KIND_BYE, 8000, "pc"), from).unwrap();
+        assert_eq!(kind, KIND_BYE);
+        assert_eq!(peer.name, "pc");
+        assert_eq!(peer.addr.port(), 8000);
+    }
+}
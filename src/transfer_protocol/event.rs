@@ -0,0 +1,121 @@
+use crate::transfer_protocol::{
+    conflict::ConflictPrompt, hashing::VerifySummary, manifest::ManifestPrompt, progress::Progress,
+    record::TransferRecord,
+};
+use rust_i18n::t;
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+/// 传输过程中发给调用方的事件，界面或其他工具据此显示日志和进度
+pub enum TransferEvent {
+    /// 握手完成
+    Started {
+        peer: SocketAddr,
+        fingerprint: String,
+    },
+    /// 开始发送或接收一个文件
+    FileStarted {
+        path: PathBuf,
+        size: u64,
+    },
+    Progress(Progress),
+    FileDone {
+        path: PathBuf,
+        status: FileStatus,
+    },
+    /// 其他需要告诉用户的信息，已按当前语言格式化
+    Log(String),
+    /// 接收端一个连接失败或一个链接无法创建；发送端的失败由 `handle_send` 的返回值给出
+    Error(String),
+    Finished {
+        summary: Option<VerifySummary>,
+        elapsed: Duration,
+    },
+    /// 接收端的一次性配对码换新了
+    PairingCode(String),
+    /// 需要用户回答的问题，回答之前对应的连接一直等待
+    Prompt(Prompt),
+    /// 接收端的一个连接结束，无论成功与否
+    Closed {
+        peer: SocketAddr,
+    },
+    /// 一次发送或一个接收连接的结果，无论成功与否都只发一次
+    Record(TransferRecord),
+}
+
+/// 单个文件的结果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    Sent,
+    /// 接收端已有同名文件并选择跳过
+    SkippedByPeer,
+    Received,
+    Mismatch,
+    /// 已有同名文件，按冲突策略跳过
+    Skipped,
+    /// 已有文件内容相同，没有重新传输
+    Identical,
+}
+
+/// 接收线程需要调用方回答的问题
+pub enum Prompt {
+    Manifest(ManifestPrompt),
+    Conflict(ConflictPrompt),
+}
+
+/// 接收传输事件，闭包 `Fn(TransferEvent)` 可以直接使用
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: TransferEvent);
+
+    fn log(&self, msg: String) {
+        self.emit(TransferEvent::Log(msg));
+    }
+}
+
+impl<F: Fn(TransferEvent) + Send + Sync> EventSink for F {
+    fn emit(&self, event: TransferEvent) {
+        self(event)
+    }
+}
+
+impl TransferEvent {
+    /// 适合写进日志的一行文字，进度、配对码、提问、连接结束和记录没有
+    pub fn log_line(&self) -> Option<String> {
+        let line = match self {
+            TransferEvent::Started { peer, fingerprint } => {
+                format!("{} : {peer} {fingerprint}", t!("peer_fingerprint"))
+            }
+            TransferEvent::FileStarted { .. } => return None,
+            TransferEvent::FileDone { path, status } => {
+                let status = match status {
+                    FileStatus::Sent => t!("send"),
+                    FileStatus::SkippedByPeer => t!("skipped_by_peer"),
+                    FileStatus::Received => t!("receive"),
+                    FileStatus::Mismatch => t!("hash_mismatch"),
+                    FileStatus::Skipped => t!("skipped_existing"),
+                    FileStatus::Identical => t!("identical"),
+                };
+                format!("{status} : {path:?}")
+            }
+            TransferEvent::Log(msg) => msg.clone(),
+            TransferEvent::Error(e) => e.clone(),
+            TransferEvent::Finished { summary, elapsed } => {
+                let time = format!("{} : {elapsed:?}", t!("time_taken"));
+                match summary {
+                    Some(summary) => format!(
+                        "{} : {}/{}\n{time}",
+                        t!("verified"),
+                        summary.verified,
+                        summary.verified + summary.failed
+                    ),
+                    None => time,
+                }
+            }
+            TransferEvent::Progress(_)
+            | TransferEvent::PairingCode(_)
+            | TransferEvent::Prompt(_)
+            | TransferEvent::Closed { .. }
+            | TransferEvent::Record(_) => return None,
+        };
+        Some(line)
+    }
+}
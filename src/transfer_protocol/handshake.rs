@@ -0,0 +1,138 @@
+use anyhow::Context;
+use rust_i18n::t;
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+const MAGIC: [u8; 4] = *b"TFDX";
+pub(super) const PROTOCOL_VERSION: u16 = 1;
+/// 能够互通的最低协议版本，低于它的对端直接拒绝
+pub(super) const MIN_PROTOCOL_VERSION: u16 = 1;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 握手时交换的能力位，双方取交集后决定启用哪些可选特性
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// 每个文件体之后附带 BLAKE3 摘要，传输结束后接收端回传校验结果
+    pub const FILE_HASH: Capabilities = Capabilities(1 << 0);
+    /// 接收端在每个文件开始前告知已有的字节数和前缀摘要，发送端据此断点续传
+    pub const RESUME: Capabilities = Capabilities(1 << 1);
+    /// 发送端先发清单，接收端确认后才开始传输
+    pub const MANIFEST: Capabilities = Capabilities(1 << 2);
+    /// 握手头之后的所有数据都经过 Noise 加密
+    pub const ENCRYPTION: Capabilities = Capabilities(1 << 3);
+    /// 加密握手前先用接收端显示的配对码做 SPAKE2 交换，得到的密钥作为 Noise 的 PSK
+    pub const PAIRING: Capabilities = Capabilities(1 << 4);
+    /// 文件体分块发送，每块带标记，发送端可以在文件中途通知取消
+    pub const CHUNKED: Capabilities = Capabilities(1 << 5);
+    /// 发送端暂停期间定时发送保活标记，接收端据此把长时间无数据的连接视为断开
+    pub const KEEPALIVE: Capabilities = Capabilities(1 << 6);
+    /// 文件大小之后附带权限位、修改和访问时间，接收端写完文件后应用
+    pub const METADATA: Capabilities = Capabilities(1 << 7);
+    /// 符号链接和硬链接作为单独的条目类型发送，接收端在保存目录内重建
+    pub const LINKS: Capabilities = Capabilities(1 << 8);
+    /// 路径用变长整数表示长度并按原始字节发送，不再限制为 u16 长度的 UTF-8
+    pub const RAW_PATHS: Capabilities = Capabilities(1 << 9);
+    /// 接收端可以在续传协商时要求跳过已存在的文件
+    pub const SKIP: Capabilities = Capabilities(1 << 10);
+
+    /// 本端支持的全部能力
+    pub const SUPPORTED: Capabilities = Capabilities(
+        Self::FILE_HASH.0
+            | Self::RESUME.0
+            | Self::MANIFEST.0
+            | Self::ENCRYPTION.0
+            | Self::PAIRING.0
+            | Self::CHUNKED.0
+            | Self::KEEPALIVE.0
+            | Self::METADATA.0
+            | Self::LINKS.0
+            | Self::RAW_PATHS.0
+            | Self::SKIP.0,
+    );
+    /// 对端缺少其中任何一项都视为不兼容，避免被降级为明文或绕过配对
+    pub const REQUIRED: Capabilities = Capabilities(Self::ENCRYPTION.0 | Self::PAIRING.0);
+
+    pub fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub fn intersection(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 & other.0)
+    }
+}
+
+/// 连接建立后由发送端调用：先发出自己的握手头，再校验接收端的回复
+pub fn client(stream: &mut TcpStream) -> anyhow::Result<Capabilities> {
+    with_timeout(stream, |stream| {
+        write_hello(stream)?;
+        let peer = read_hello(stream)?;
+        check_version(peer)
+    })
+}
+
+/// 接收端收到连接后调用：魔数不对时不做任何回复，版本不兼容时仍回复握手头，方便对端给出明确错误
+pub fn server(stream: &mut TcpStream) -> anyhow::Result<Capabilities> {
+    with_timeout(stream, |stream| {
+        let peer = read_hello(stream)?;
+        write_hello(stream)?;
+        check_version(peer)
+    })
+}
+
+pub(super) fn with_timeout<T>(
+    stream: &mut TcpStream,
+    f: impl FnOnce(&mut TcpStream) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let result = f(stream);
+    stream.set_read_timeout(None)?;
+    result
+}
+
+fn write_hello(stream: &mut impl Write) -> anyhow::Result<()> {
+    let mut hello = Vec::with_capacity(10);
+    hello.extend_from_slice(&MAGIC);
+    hello.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    hello.extend_from_slice(&Capabilities::SUPPORTED.0.to_be_bytes());
+
+    stream.write_all(&hello)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn read_hello(stream: &mut impl Read) -> anyhow::Result<(u16, Capabilities)> {
+    let mut hello = [0; 10];
+    stream
+        .read_exact(&mut hello)
+        .with_context(|| t!("incompatible_peer"))?;
+
+    if hello[0..4] != MAGIC {
+        anyhow::bail!("{} : {:02x?}", t!("incompatible_peer"), &hello[0..4]);
+    }
+    let version = u16::from_be_bytes([hello[4], hello[5]]);
+    let capabilities = u32::from_be_bytes([hello[6], hello[7], hello[8], hello[9]]);
+
+    Ok((version, Capabilities(capabilities)))
+}
+
+fn check_version((version, capabilities): (u16, Capabilities)) -> anyhow::Result<Capabilities> {
+    if version < MIN_PROTOCOL_VERSION {
+        anyhow::bail!(
+            "{} : {} v{version} < v{MIN_PROTOCOL_VERSION}",
+            t!("incompatible_peer"),
+            t!("protocol_version")
+        );
+    }
+    if !capabilities.contains(Capabilities::REQUIRED) {
+        anyhow::bail!(
+            "{} : {:#x}",
+            t!("incompatible_peer"),
+            Capabilities::REQUIRED.0 & !capabilities.0
+        );
+    }
+    Ok(Capabilities::SUPPORTED.intersection(capabilities))
+}
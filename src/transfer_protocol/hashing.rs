@@ -0,0 +1,97 @@
+use std::io::{Read, Seek, Write};
+
+pub const HASH_LEN: usize = blake3::OUT_LEN;
+
+/// 读取时顺带计算 BLAKE3 摘要
+pub struct HashReader<R: Read> {
+    inner: R,
+    hasher: blake3::Hasher,
+}
+
+impl<R: Read> HashReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: blake3::Hasher::new(),
+        }
+    }
+    pub fn finalize(&self) -> [u8; HASH_LEN] {
+        *self.hasher.finalize().as_bytes()
+    }
+}
+
+impl<R: Read + Seek> HashReader<R> {
+    /// 回到开头并丢弃已计算的摘要
+    pub fn rewind(&mut self) -> std::io::Result<()> {
+        self.inner.rewind()?;
+        self.hasher.reset();
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// 写入时顺带计算 BLAKE3 摘要
+pub struct HashWriter<W: Write> {
+    inner: W,
+    hasher: blake3::Hasher,
+}
+
+impl<W: Write> HashWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_hasher(inner, blake3::Hasher::new())
+    }
+    /// 续传时沿用已有前缀的摘要状态
+    pub fn with_hasher(inner: W, hasher: blake3::Hasher) -> Self {
+        Self { inner, hasher }
+    }
+    pub fn finalize(&self) -> [u8; HASH_LEN] {
+        *self.hasher.finalize().as_bytes()
+    }
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 接收端在传输结束后回传给发送端的校验结果
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VerifySummary {
+    pub verified: u64,
+    pub failed: u64,
+}
+
+impl VerifySummary {
+    pub fn to_bytes(self) -> [u8; 16] {
+        let mut buf = [0; 16];
+        buf[..8].copy_from_slice(&self.verified.to_be_bytes());
+        buf[8..].copy_from_slice(&self.failed.to_be_bytes());
+        buf
+    }
+    pub fn from_bytes(buf: [u8; 16]) -> Self {
+        let mut verified = [0; 8];
+        let mut failed = [0; 8];
+        verified.copy_from_slice(&buf[..8]);
+        failed.copy_from_slice(&buf[8..]);
+        Self {
+            verified: u64::from_be_bytes(verified),
+            failed: u64::from_be_bytes(failed),
+        }
+    }
+}
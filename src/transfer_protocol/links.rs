@@ -0,0 +1,194 @@
+use rust_i18n::t;
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    path::{Component, Path, PathBuf},
+};
+
+/// 记录链接数大于 1 的文件，同一个 inode 只发送一次，之后的路径作为硬链接发送
+#[derive(Debug, Default)]
+pub struct HardLinks {
+    seen: HashMap<(u64, u64), PathBuf>,
+}
+
+impl HardLinks {
+    /// 第一次见到时记下并返回 `None`，之后返回第一次出现时的路径
+    #[cfg(unix)]
+    pub fn check(&mut self, metadata: &Metadata, path: &Path) -> Option<PathBuf> {
+        use std::{collections::hash_map::Entry, os::unix::fs::MetadataExt};
+
+        if metadata.nlink() < 2 {
+            return None;
+        }
+        match self.seen.entry((metadata.dev(), metadata.ino())) {
+            Entry::Occupied(first) => Some(first.get().clone()),
+            Entry::Vacant(entry) => {
+                entry.insert(path.to_path_buf());
+                None
+            }
+        }
+    }
+
+    /// 标准库在其他平台上取不到稳定的文件编号，硬链接按普通文件发送
+    #[cfg(not(unix))]
+    pub fn check(&mut self, _metadata: &Metadata, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// 在保存目录内重建符号链接。链接目标必须是相对路径，`..` 只能出现在开头，
+/// 并且从链接所在目录的真实路径出发不能跳出保存目录
+pub fn create_symlink(save_path: &Path, link: &Path, target: &Path) -> anyhow::Result<()> {
+    let parent = link.parent().unwrap_or(save_path);
+    fs::create_dir_all(parent)?;
+
+    let mut resolved = parent.canonicalize()?;
+    let mut leading = true;
+    for component in target.components() {
+        match component {
+            Component::ParentDir if leading => {
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                leading = false;
+                resolved.push(name);
+            }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                anyhow::bail!("{} : {link:?} -> {target:?}", t!("unsafe_link"));
+            }
+        }
+        if !resolved.starts_with(save_path) {
+            anyhow::bail!("{} : {link:?} -> {target:?}", t!("unsafe_link"));
+        }
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link)?;
+    #[cfg(windows)]
+    if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)?;
+    } else {
+        std::os::windows::fs::symlink_file(target, link)?;
+    }
+    Ok(())
+}
+
+/// 把之前收到的 `source` 硬链接到 `link`，文件系统不支持时退回复制
+pub fn create_hard_link(source: &Path, link: &Path) -> anyhow::Result<()> {
+    if !source.is_file() {
+        anyhow::bail!("{} : {link:?} -> {source:?}", t!("unsafe_link"));
+    }
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // 复制会覆盖已有文件，已有条目由调用方按冲突策略先处理
+    if link.symlink_metadata().is_ok() {
+        anyhow::bail!("{} : {link:?}", t!("file_exists"));
+    }
+
+    if fs::hard_link(source, link).is_err() {
+        fs::copy(source, link)?;
+    }
+    Ok(())
+}
+
+/// 冲突策略决定覆盖时删除同名的文件或链接，不会删除目录
+pub fn remove_existing(path: &Path) -> anyhow::Result<()> {
+    match path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => {
+            anyhow::bail!("{} : {path:?}", t!("unsafe_link"));
+        }
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+/// 两个路径指向的文件内容相同，用于判断已有文件能否当作这个硬链接
+pub fn same_content(a: &Path, b: &Path) -> bool {
+    let hash = |path: &Path| -> std::io::Result<blake3::Hash> {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_reader(fs::File::open(path)?)?;
+        Ok(hasher.finalize())
+    };
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(ma), Ok(mb)) if ma.is_file() && mb.is_file() && ma.len() == mb.len() => {
+            matches!((hash(a), hash(b)), (Ok(ha), Ok(hb)) if ha == hb)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn save_dir() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let save = dir.path().join("save");
+        fs::create_dir_all(save.join("sub")).unwrap();
+        let save = save.canonicalize().unwrap();
+        (dir, save)
+    }
+
+    #[test]
+    fn symlink_rejects_escaping_targets() {
+        let (_dir, save) = save_dir();
+        let link = save.join("sub/link");
+        for target in [
+            "../../outside",
+            "/etc/passwd",
+            "a/../../../x",
+            "../a/../../x",
+        ] {
+            assert!(
+                create_symlink(&save, &link, Path::new(target)).is_err(),
+                "{target}"
+            );
+            assert!(link.symlink_metadata().is_err());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_allows_targets_inside() {
+        let (_dir, save) = save_dir();
+        let link = save.join("sub/link");
+        create_symlink(&save, &link, Path::new("../file.txt")).unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("../file.txt"));
+    }
+
+    #[test]
+    fn symlink_keeps_existing_file() {
+        let (_dir, save) = save_dir();
+        let link = save.join("sub/link");
+        fs::write(&link, "local").unwrap();
+        assert!(create_symlink(&save, &link, Path::new("other")).is_err());
+        assert_eq!(fs::read_to_string(&link).unwrap(), "local");
+    }
+
+    #[test]
+    fn hard_link_keeps_existing_file() {
+        let (_dir, save) = save_dir();
+        let source = save.join("source.txt");
+        let link = save.join("sub/link.txt");
+        fs::write(&source, "received").unwrap();
+        fs::write(&link, "local").unwrap();
+
+        assert!(create_hard_link(&source, &link).is_err());
+        assert_eq!(fs::read_to_string(&link).unwrap(), "local");
+
+        fs::remove_file(&link).unwrap();
+        create_hard_link(&source, &link).unwrap();
+        assert_eq!(fs::read_to_string(&link).unwrap(), "received");
+        assert!(same_content(&source, &link));
+    }
+
+    #[test]
+    fn hard_link_needs_a_file_source() {
+        let (_dir, save) = save_dir();
+        assert!(create_hard_link(&save.join("missing"), &save.join("link")).is_err());
+        assert!(create_hard_link(&save.join("sub"), &save.join("link")).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::Context;
+use std::{
+    io::{Read, Write},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::mpsc::Sender,
+};
+
+const DECISION_REJECT: u8 = 0;
+const DECISION_ACCEPT: u8 = 1;
+
+/// 发送端在传输任何文件之前发给接收端的清单
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Manifest {
+    pub file_count: u64,
+    pub total_size: u64,
+    pub top_level_names: Vec<String>,
+    pub hostname: String,
+}
+
+impl Manifest {
+    pub fn write_to(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        writer.write_all(&self.file_count.to_be_bytes())?;
+        writer.write_all(&self.total_size.to_be_bytes())?;
+        write_string(writer, &self.hostname)?;
+
+        writer.write_all(&(self.top_level_names.len() as u32).to_be_bytes())?;
+        for name in &self.top_level_names {
+            write_string(writer, name)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> anyhow::Result<Self> {
+        let mut buf = [0; 8];
+        reader.read_exact(&mut buf)?;
+        let file_count = u64::from_be_bytes(buf);
+        reader.read_exact(&mut buf)?;
+        let total_size = u64::from_be_bytes(buf);
+        let hostname = read_string(reader)?;
+
+        let mut len = [0; 4];
+        reader.read_exact(&mut len)?;
+        let top_level_names = (0..u32::from_be_bytes(len))
+            .map(|_| read_string(reader))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            file_count,
+            total_size,
+            top_level_names,
+            hostname,
+        })
+    }
+}
+
+/// 接收端用户对清单的决定
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decision {
+    /// 接受并保存到给定目录
+    Accept(PathBuf),
+    Reject,
+}
+
+impl Decision {
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            Decision::Accept(_) => DECISION_ACCEPT,
+            Decision::Reject => DECISION_REJECT,
+        }
+    }
+    pub fn is_accepted(byte: u8) -> bool {
+        byte == DECISION_ACCEPT
+    }
+}
+
+/// 交给界面弹窗的确认请求，用户的决定通过 `reply` 传回接收线程
+pub struct ManifestPrompt {
+    pub peer: SocketAddr,
+    pub manifest: Manifest,
+    pub save_path: PathBuf,
+    pub reply: Sender<Decision>,
+}
+
+fn write_string(writer: &mut impl Write, s: &str) -> anyhow::Result<()> {
+    let bytes = s.as_bytes();
+    let len = u16::try_from(bytes.len()).with_context(|| format!("{s:?}"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+fn read_string(reader: &mut impl Read) -> anyhow::Result<String> {
+    let mut len = [0; 2];
+    reader.read_exact(&mut len)?;
+    let mut buf = vec![0; u16::from_be_bytes(len) as usize];
+    reader.read_exact(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
@@ -0,0 +1,151 @@
+use std::{
+    fs::{self, File, FileTimes, Metadata},
+    io::{Read, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const HAS_MODE: u8 = 1 << 0;
+const HAS_MODIFIED: u8 = 1 << 1;
+const HAS_ACCESSED: u8 = 1 << 2;
+const READONLY: u8 = 1 << 3;
+
+/// 只传递读写执行权限位，不传递 setuid、setgid 和粘滞位
+const MODE_MASK: u32 = 0o777;
+
+/// 随文件一起传输的元数据，平台取不到的字段为 `None`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FileMetadata {
+    /// Unix 权限位
+    pub mode: Option<u32>,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub readonly: bool,
+}
+
+impl FileMetadata {
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        #[cfg(unix)]
+        let mode =
+            Some(std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()) & MODE_MASK);
+        #[cfg(not(unix))]
+        let mode = None;
+
+        Self {
+            mode,
+            modified: metadata.modified().ok(),
+            accessed: metadata.accessed().ok(),
+            readonly: metadata.permissions().readonly(),
+        }
+    }
+
+    /// 1 字节标志位，u32 权限位，修改时间和访问时间各为 u64 秒加 u32 纳秒
+    pub fn write_to(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        let modified = self.modified.and_then(since_epoch);
+        let accessed = self.accessed.and_then(since_epoch);
+
+        let mut flags = 0;
+        if self.mode.is_some() {
+            flags |= HAS_MODE;
+        }
+        if modified.is_some() {
+            flags |= HAS_MODIFIED;
+        }
+        if accessed.is_some() {
+            flags |= HAS_ACCESSED;
+        }
+        if self.readonly {
+            flags |= READONLY;
+        }
+
+        writer.write_all(&[flags])?;
+        writer.write_all(&self.mode.unwrap_or(0).to_be_bytes())?;
+        write_time(writer, modified.unwrap_or_default())?;
+        write_time(writer, accessed.unwrap_or_default())?;
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> anyhow::Result<Self> {
+        let mut flags = [0];
+        reader.read_exact(&mut flags)?;
+        let flags = flags[0];
+
+        let mut mode = [0; 4];
+        reader.read_exact(&mut mode)?;
+        let modified = read_time(reader)?;
+        let accessed = read_time(reader)?;
+
+        Ok(Self {
+            mode: (flags & HAS_MODE != 0).then_some(u32::from_be_bytes(mode) & MODE_MASK),
+            modified: (flags & HAS_MODIFIED != 0).then_some(modified),
+            accessed: (flags & HAS_ACCESSED != 0).then_some(accessed),
+            readonly: flags & READONLY != 0,
+        })
+    }
+
+    /// 文件体写完后调用，先设置时间再设置权限，否则只读文件无法再打开
+    pub fn apply(&self, path: &Path) -> std::io::Result<()> {
+        let mut times = FileTimes::new();
+        if let Some(modified) = self.modified {
+            times = times.set_modified(modified);
+        }
+        if let Some(accessed) = self.accessed {
+            times = times.set_accessed(accessed);
+        }
+        File::options().write(true).open(path)?.set_times(times)?;
+
+        let mut permissions = fs::metadata(path)?.permissions();
+        match self.mode {
+            #[cfg(unix)]
+            Some(mode) => std::os::unix::fs::PermissionsExt::set_mode(&mut permissions, mode),
+            // 没有权限位时只能设置只读，Unix 上清除只读会让所有人可写，因此不做
+            _ if self.readonly => permissions.set_readonly(true),
+            _ => return Ok(()),
+        }
+        fs::set_permissions(path, permissions)
+    }
+}
+
+/// 覆盖或续传之前让已有的只读文件重新可写
+pub fn make_writable(path: &Path) -> std::io::Result<()> {
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(());
+    };
+    let mut permissions = metadata.permissions();
+    if !permissions.readonly() {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(false);
+
+    fs::set_permissions(path, permissions)
+}
+
+/// 早于 1970 年的时间不传递
+fn since_epoch(time: SystemTime) -> Option<Duration> {
+    time.duration_since(UNIX_EPOCH).ok()
+}
+
+fn write_time(writer: &mut impl Write, time: Duration) -> std::io::Result<()> {
+    writer.write_all(&time.as_secs().to_be_bytes())?;
+    writer.write_all(&time.subsec_nanos().to_be_bytes())
+}
+
+fn read_time(reader: &mut impl Read) -> anyhow::Result<SystemTime> {
+    let mut secs = [0; 8];
+    reader.read_exact(&mut secs)?;
+    let mut nanos = [0; 4];
+    reader.read_exact(&mut nanos)?;
+
+    let nanos = u32::from_be_bytes(nanos).min(999_999_999);
+    let since_epoch = Duration::new(u64::from_be_bytes(secs), nanos);
+    UNIX_EPOCH
+        .checked_add(since_epoch)
+        .ok_or_else(|| anyhow::anyhow!("无效的时间 : {since_epoch:?}"))
+}
@@ -1,7 +1,46 @@
 const TYPE_FILE: u8 = 0;
 const TYPE_DIR: u8 = 1;
+/// 发送端在条目之间被取消
+const TYPE_ABORT: u8 = 2;
+/// 发送端在条目之间暂停时的保活标记
+const TYPE_KEEPALIVE: u8 = 3;
+/// 路径之后是链接目标
+const TYPE_SYMLINK: u8 = 4;
+/// 路径之后是同一文件第一次出现时的路径
+const TYPE_HARDLINK: u8 = 5;
 
+const RESUME_NONE: u8 = 0;
+const RESUME_FROM: u8 = 1;
+/// 接收端已有同名文件且选择跳过，发送端不再发送这个文件
+const RESUME_SKIP: u8 = 2;
+
+/// 分块文件体的块标记，数据块后跟 u32 长度和数据
+const CHUNK_DATA: u8 = 0;
+const CHUNK_END: u8 = 1;
+const CHUNK_ABORT: u8 = 2;
+const CHUNK_KEEPALIVE: u8 = 3;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub mod conflict;
+pub mod control;
+pub mod discovery;
+pub mod event;
+pub mod handshake;
+pub mod hashing;
+pub mod links;
+pub mod manifest;
+pub mod metadata;
+pub mod pairing;
+pub mod path_codec;
+pub mod progress;
 pub mod receive_protocol;
+pub mod record;
+pub mod sanitize;
+pub mod secure_stream;
 pub mod send_protocol;
+pub mod target;
+pub mod temp_file;
 
+mod progress_reader;
 mod progress_writer;
+pub use progress_writer::format_size;
@@ -0,0 +1,149 @@
+use crate::transfer_protocol::{
+    event::{EventSink, TransferEvent},
+    handshake::with_timeout,
+};
+use rand::Rng;
+use rust_i18n::t;
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+};
+
+const PAKE_IDENTITY: &[u8] = b"transfer-dioxus pairing";
+/// SPAKE2 Ed25519 消息长度：1 字节标识加 32 字节群元素
+const PAKE_MESSAGE_LEN: usize = 33;
+pub const SESSION_KEY_LEN: usize = 32;
+
+const PAIRING_OK: u8 = 1;
+const PAIRING_WRONG_CODE: u8 = 0;
+
+const ADJECTIVES: [&str; 32] = [
+    "amber", "bold", "brave", "bright", "calm", "clever", "crimson", "curious", "dusty", "eager",
+    "fancy", "gentle", "golden", "happy", "hidden", "humble", "icy", "jolly", "lucky", "mellow",
+    "misty", "noble", "olive", "proud", "quiet", "rapid", "rusty", "silver", "sleepy", "sunny",
+    "swift", "velvet",
+];
+const NOUNS: [&str; 32] = [
+    "badger", "beacon", "canyon", "comet", "falcon", "forest", "galaxy", "harbor", "heron",
+    "island", "jaguar", "lantern", "meadow", "meteor", "nebula", "orchid", "otter", "panda",
+    "pepper", "pine", "planet", "puffin", "raven", "river", "rocket", "saturn", "spruce", "tiger",
+    "tulip", "walrus", "willow", "zebra",
+];
+
+/// 接收端当前的一次性配对码，每被尝试一次就换新，新码通过事件交给界面显示
+#[derive(Clone)]
+pub struct PairingCode {
+    current: Arc<Mutex<String>>,
+    events: Arc<dyn EventSink>,
+    /// 调用方指定的配对码在整个接收会话内不变
+    fixed: bool,
+}
+
+impl PairingCode {
+    pub fn new(events: Arc<dyn EventSink>) -> Self {
+        let code = generate_code();
+        events.emit(TransferEvent::PairingCode(code.clone()));
+        Self {
+            current: Arc::new(Mutex::new(code)),
+            events,
+            fixed: false,
+        }
+    }
+
+    /// 使用调用方给定的配对码，不再换新，供脚本同时控制收发两端
+    pub fn fixed(code: String, events: Arc<dyn EventSink>) -> Self {
+        Self {
+            current: Arc::new(Mutex::new(code)),
+            events,
+            fixed: true,
+        }
+    }
+
+    /// 取出当前的配对码并立即换新，每个码只能被尝试一次；固定的配对码原样返回
+    pub fn take(&self) -> String {
+        let mut current = self.current.lock().unwrap();
+        if self.fixed {
+            return current.clone();
+        }
+        let code = generate_code();
+        self.events.emit(TransferEvent::PairingCode(code.clone()));
+        std::mem::replace(&mut *current, code)
+    }
+}
+
+/// 形如 "7-crimson-otter" 的短码
+pub fn generate_code() -> String {
+    let mut rng = rand::rng();
+    format!(
+        "{}-{}-{}",
+        rng.random_range(0..100),
+        ADJECTIVES[rng.random_range(0..ADJECTIVES.len())],
+        NOUNS[rng.random_range(0..NOUNS.len())]
+    )
+}
+
+/// 忽略大小写和首尾空白，空格视同连字符
+fn normalize(code: &str) -> String {
+    code.trim()
+        .to_lowercase()
+        .split(|c: char| c == '-' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// 发送端：用用户输入的配对码完成 SPAKE2 交换并证明自己知道配对码
+pub fn client(stream: &mut TcpStream, code: &str) -> anyhow::Result<[u8; SESSION_KEY_LEN]> {
+    with_timeout(stream, |stream| {
+        let key = exchange(stream, code)?;
+
+        stream.write_all(blake3::keyed_hash(&key, b"sender").as_bytes())?;
+        stream.flush()?;
+
+        let mut result = [0];
+        stream.read_exact(&mut result)?;
+        if result[0] != PAIRING_OK {
+            anyhow::bail!(t!("wrong_pairing_code"));
+        }
+        Ok(key)
+    })
+}
+
+/// 接收端：校验对端是否知道当前配对码
+pub fn server(stream: &mut TcpStream, code: &str) -> anyhow::Result<[u8; SESSION_KEY_LEN]> {
+    with_timeout(stream, |stream| {
+        let key = exchange(stream, code)?;
+
+        let mut confirm = [0; blake3::OUT_LEN];
+        stream.read_exact(&mut confirm)?;
+        if blake3::keyed_hash(&key, b"sender") != confirm {
+            stream.write_all(&[PAIRING_WRONG_CODE])?;
+            anyhow::bail!(t!("wrong_pairing_code"));
+        }
+
+        stream.write_all(&[PAIRING_OK])?;
+        stream.flush()?;
+        Ok(key)
+    })
+}
+
+fn exchange(stream: &mut TcpStream, code: &str) -> anyhow::Result<[u8; SESSION_KEY_LEN]> {
+    let (pake, message) = Spake2::<Ed25519Group>::start_symmetric(
+        &Password::new(normalize(code).as_bytes()),
+        &Identity::new(PAKE_IDENTITY),
+    );
+    stream.write_all(&message)?;
+    stream.flush()?;
+
+    let mut peer_message = [0; PAKE_MESSAGE_LEN];
+    stream.read_exact(&mut peer_message)?;
+
+    let key = pake
+        .finish(&peer_message)
+        .map_err(|e| anyhow::anyhow!("{} : {e}", t!("wrong_pairing_code")))?;
+    let mut session_key = [0; SESSION_KEY_LEN];
+    session_key.copy_from_slice(&key[..SESSION_KEY_LEN]);
+    Ok(session_key)
+}
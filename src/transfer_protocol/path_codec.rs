@@ -0,0 +1,176 @@
+use std::{
+    borrow::Cow,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// 远超任何文件系统的路径上限，只用来拒绝损坏的长度
+const MAX_PATH_LEN: u64 = 1 << 20;
+
+/// 路径编码为 LEB128 变长整数表示的字节数加原始字节，分隔符统一为 `/`
+///
+/// Unix 上发送文件名的原始字节，不要求是 UTF-8；Windows 上的文件名转为 UTF-8，
+/// 无法配对的代理项替换为 U+FFFD
+pub fn write_path(writer: &mut impl Write, path: &Path) -> std::io::Result<()> {
+    let bytes = path_to_bytes(path);
+    write_varint(writer, bytes.len() as u64)?;
+    writer.write_all(&bytes)
+}
+
+/// Unix 上原样还原字节；其他平台上合法的 UTF-8 按 UTF-8 解码，
+/// 否则整个文件名按 Latin-1 逐字节解码，旧系统上的 Latin-1 文件名因此仍然可读且不会相互冲突
+pub fn read_path(reader: &mut impl Read) -> anyhow::Result<PathBuf> {
+    let len = read_varint(reader)?;
+    if len > MAX_PATH_LEN {
+        anyhow::bail!("路径过长 : {len}");
+    }
+
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes_to_path(bytes))
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    Cow::Owned(path.to_string_lossy().replace('\\', "/").into_bytes())
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    match String::from_utf8(bytes) {
+        Ok(path) => PathBuf::from(path),
+        Err(e) => e
+            .into_bytes()
+            .into_iter()
+            .map(char::from)
+            .collect::<String>()
+            .into(),
+    }
+}
+
+fn write_varint(writer: &mut impl Write, mut value: u64) -> std::io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(reader: &mut impl Read) -> anyhow::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    anyhow::bail!("变长整数过长")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint_bytes(value: u64) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, value).unwrap();
+        buf
+    }
+
+    fn round_trip(path: &Path) -> PathBuf {
+        let mut buf = Vec::new();
+        write_path(&mut buf, path).unwrap();
+        let mut reader = buf.as_slice();
+        let decoded = read_path(&mut reader).unwrap();
+        assert!(reader.is_empty());
+        decoded
+    }
+
+    #[test]
+    fn varint_boundaries() {
+        for (value, len) in [
+            (0, 1),
+            (127, 1),
+            (128, 2),
+            (16383, 2),
+            (16384, 3),
+            (u64::MAX, 10),
+        ] {
+            let bytes = varint_bytes(value);
+            assert_eq!(bytes.len(), len, "{value}");
+            assert_eq!(read_varint(&mut bytes.as_slice()).unwrap(), value);
+        }
+        assert_eq!(varint_bytes(127), [0x7f]);
+        assert_eq!(varint_bytes(128), [0x80, 0x01]);
+        assert_eq!(varint_bytes(16383), [0xff, 0x7f]);
+        assert_eq!(varint_bytes(16384), [0x80, 0x80, 0x01]);
+    }
+
+    #[test]
+    fn rejects_overlong_varint() {
+        assert!(read_varint(&mut [0x80; 11].as_slice()).is_err());
+        assert!(read_varint(&mut [0x80].as_slice()).is_err());
+    }
+
+    #[test]
+    fn round_trips_paths() {
+        for path in ["a.txt", "dir/sub/file", "中文/文件名.txt"] {
+            assert_eq!(round_trip(Path::new(path)), Path::new(path));
+        }
+    }
+
+    /// 旧协议用 u16 表示长度，超过 65535 字节的路径只能用变长整数表示
+    #[test]
+    fn round_trips_path_longer_than_u16() {
+        let path = PathBuf::from("a/".repeat(40_000) + "end");
+        assert!(path.as_os_str().len() > 65535);
+        assert_eq!(round_trip(&path), path);
+    }
+
+    #[test]
+    fn rejects_corrupt_length() {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, MAX_PATH_LEN + 1).unwrap();
+        assert!(read_path(&mut buf.as_slice()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn round_trips_non_utf8_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"caf\xe9/\xff\xfe.bin"));
+        assert!(path.to_str().is_none());
+        assert_eq!(round_trip(path), path);
+    }
+
+    /// 其他平台上不是 UTF-8 的字节按 Latin-1 解码
+    #[cfg(not(unix))]
+    #[test]
+    fn decodes_non_utf8_bytes_as_latin1() {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, 4).unwrap();
+        buf.extend_from_slice(b"caf\xe9");
+        assert_eq!(
+            read_path(&mut buf.as_slice()).unwrap(),
+            Path::new("caf\u{e9}")
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// 至少间隔这么久才报告一次进度
+const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+/// 瞬时速度的平滑系数，越大越跟手，越小越平稳
+const SPEED_SMOOTHING: f64 = 0.5;
+
+/// 一次传输的进度，速度单位为字节每秒
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Progress {
+    /// 对端地址，接收端同时有多个连接时据此区分
+    pub peer: Option<SocketAddr>,
+    /// 已处理的字节数，续传和跳过的部分也计入
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_done: u64,
+    pub files_total: u64,
+    pub current_path: Option<PathBuf>,
+    /// 最近一段时间的速度
+    pub current_speed: f64,
+    /// 从开始到现在的平均速度，不含续传和跳过的部分
+    pub average_speed: f64,
+    /// 按最近的速度估算的剩余时间，还没有速度或不知道总量时为 `None`
+    pub eta: Option<Duration>,
+}
+
+impl Progress {
+    pub fn percent(&self) -> f64 {
+        if self.bytes_total > 0 {
+            self.bytes_done as f64 / self.bytes_total as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// 统计已传输的字节和文件数并计算速度，发送端和接收端共用
+pub struct ProgressTracker {
+    progress: Progress,
+    /// 实际经过网络传输的字节数，用来计算速度
+    bytes_transferred: u64,
+    start_time: Instant,
+    last_report: Instant,
+    last_bytes: u64,
+}
+
+impl ProgressTracker {
+    pub fn new(peer: SocketAddr, files_total: u64, bytes_total: u64) -> Self {
+        Self {
+            progress: Progress {
+                peer: Some(peer),
+                files_total,
+                bytes_total,
+                ..Progress::default()
+            },
+            bytes_transferred: 0,
+            start_time: Instant::now(),
+            last_report: Instant::now(),
+            last_bytes: 0,
+        }
+    }
+
+    pub fn set_total(&mut self, files_total: u64, bytes_total: u64) {
+        self.progress.files_total = files_total;
+        self.progress.bytes_total = bytes_total;
+    }
+    pub fn add(&mut self, n: u64) {
+        self.bytes_transferred += n;
+        self.progress.bytes_done += n;
+    }
+    /// 续传或跳过的字节只计入进度，不计入速度
+    pub fn skip(&mut self, n: u64) {
+        self.progress.bytes_done += n;
+    }
+    pub fn start_file(&mut self, path: &Path) {
+        self.progress.current_path = Some(path.to_path_buf());
+    }
+    pub fn finish_file(&mut self) {
+        self.progress.files_done += 1;
+        self.progress.current_path = None;
+    }
+    /// 暂停的时长不计入用时和速度
+    pub fn exclude(&mut self, paused: Duration) {
+        self.start_time += paused;
+        self.last_report += paused;
+    }
+    pub fn elapsed(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    /// 距上次报告足够久时返回最新进度
+    pub fn poll(&mut self) -> Option<Progress> {
+        (self.last_report.elapsed() >= REPORT_INTERVAL).then(|| self.snapshot())
+    }
+
+    /// 立即计算并返回最新进度
+    pub fn snapshot(&mut self) -> Progress {
+        let since_last = self.last_report.elapsed().as_secs_f64();
+        if since_last > 0.0 {
+            let speed = (self.bytes_transferred - self.last_bytes) as f64 / since_last;
+            self.progress.current_speed = if self.progress.current_speed > 0.0 {
+                SPEED_SMOOTHING * speed + (1.0 - SPEED_SMOOTHING) * self.progress.current_speed
+            } else {
+                speed
+            };
+        }
+        self.last_report = Instant::now();
+        self.last_bytes = self.bytes_transferred;
+
+        let elapsed = self.start_time.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.progress.average_speed = self.bytes_transferred as f64 / elapsed;
+        }
+
+        let remaining = self
+            .progress
+            .bytes_total
+            .saturating_sub(self.progress.bytes_done);
+        let speed = if self.progress.current_speed > 0.0 {
+            self.progress.current_speed
+        } else {
+            self.progress.average_speed
+        };
+        // 对端没有发清单时不知道总量，无法估算
+        self.progress.eta = if self.progress.bytes_total == 0 {
+            None
+        } else if remaining == 0 {
+            Some(Duration::ZERO)
+        } else {
+            (speed > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / speed))
+        };
+
+        self.progress.clone()
+    }
+}
+
+/// 形如 "1:02:03" 或 "02:03" 的时长
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{secs:02}")
+    } else {
+        format!("{minutes:02}:{secs:02}")
+    }
+}
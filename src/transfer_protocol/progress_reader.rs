@@ -0,0 +1,69 @@
+use crate::transfer_protocol::{
+    event::{EventSink, TransferEvent},
+    progress::ProgressTracker,
+};
+use std::{io::Read, path::Path, sync::Arc};
+
+/// 接收端的进度统计，只有开启监控时读到的字节（文件体）计入进度
+pub struct ProgressReader<R: Read> {
+    inner: R,
+    tracker: ProgressTracker,
+    events: Arc<dyn EventSink>,
+    monitor: bool,
+}
+
+impl<R: Read> ProgressReader<R> {
+    pub fn new(inner: R, tracker: ProgressTracker, events: Arc<dyn EventSink>) -> Self {
+        Self {
+            inner,
+            tracker,
+            events,
+            monitor: false,
+        }
+    }
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+    pub fn start_monitor(&mut self) {
+        self.monitor = true;
+    }
+    pub fn stop_monitor(&mut self) {
+        self.monitor = false;
+    }
+    /// 收到清单后才知道总量
+    pub fn set_total(&mut self, files_total: u64, bytes_total: u64) {
+        self.tracker.set_total(files_total, bytes_total);
+    }
+    /// 续传和跳过的字节只计入进度，不计入速度
+    pub fn skip(&mut self, n: u64) {
+        self.tracker.skip(n);
+    }
+    pub fn start_file(&mut self, path: &Path) {
+        self.tracker.start_file(path);
+    }
+    pub fn finish_file(&mut self) {
+        self.tracker.finish_file();
+    }
+    pub fn send_process(&mut self) {
+        let progress = self.tracker.snapshot();
+        self.events.emit(TransferEvent::Progress(progress));
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+
+        if self.monitor {
+            self.tracker.add(n as u64);
+        }
+
+        if let Some(progress) = self.tracker.poll() {
+            self.events.emit(TransferEvent::Progress(progress));
+        }
+        Ok(n)
+    }
+}
@@ -1,68 +1,69 @@
-use dioxus::hooks::UnboundedSender;
+use crate::transfer_protocol::{
+    event::{EventSink, TransferEvent},
+    progress::ProgressTracker,
+};
 use std::{
     io::Write,
-    time::{Duration, Instant},
+    path::Path,
+    sync::Arc,
+    time::Duration,
 };
 
 pub struct ProgressWriter<W: Write> {
     inner: W,
-    total_size: u64,
-    bytes_send: u64,
-    last_send_time: Instant,
-    start_time: Instant,
-    progress_tx: UnboundedSender<(f64, String)>,
+    tracker: ProgressTracker,
+    events: Arc<dyn EventSink>,
     monitor: bool,
 }
 
 impl<W: Write> ProgressWriter<W> {
-    pub fn new(inner: W, total_size: u64, progress_tx: UnboundedSender<(f64, String)>) -> Self {
+    pub fn new(inner: W, tracker: ProgressTracker, events: Arc<dyn EventSink>) -> Self {
         Self {
             inner,
-            total_size,
-            bytes_send: 0,
-            last_send_time: Instant::now(),
-            start_time: Instant::now(),
-            progress_tx,
+            tracker,
+            events,
             monitor: false,
         }
     }
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+    pub fn events(&self) -> &dyn EventSink {
+        &*self.events
+    }
     pub fn start_monitor(&mut self) {
         self.monitor = true;
     }
     pub fn stop_monitor(&mut self) {
         self.monitor = false;
     }
+    /// 分块发送时直接计入文件数据的字节数，块标记和长度不算
+    pub fn add(&mut self, n: u64) {
+        self.tracker.add(n);
+    }
+    /// 续传跳过的字节只计入进度，不计入速度
+    pub fn skip(&mut self, n: u64) {
+        self.tracker.skip(n);
+    }
+    pub fn start_file(&mut self, path: &Path) {
+        self.tracker.start_file(path);
+    }
+    pub fn finish_file(&mut self) {
+        self.tracker.finish_file();
+    }
+    /// 暂停的时长不计入用时和速度
+    pub fn exclude(&mut self, paused: Duration) {
+        self.tracker.exclude(paused);
+    }
     pub fn total_time(&self) -> Duration {
-        self.start_time.elapsed()
-    }
-    pub fn send_process(&self) {
-        let percentage = if self.total_size > 0 {
-            (self.bytes_send as f64 / self.total_size as f64) * 100.0
-        } else {
-            0.0
-        };
-
-        let elapsed_secs = self.start_time.elapsed().as_secs_f64();
-        let speed = if elapsed_secs > 0.0 {
-            self.bytes_send as f64 / elapsed_secs
-        } else {
-            0.0
-        };
-        let mut speed = Self::format_size(speed);
-        speed.push_str("/s");
-
-        _ = self.progress_tx.unbounded_send((percentage, speed));
+        self.tracker.elapsed()
     }
-
-    fn format_size(mut size: f64) -> String {
-        let display = ["B", "KB", "MB", "GB", "TB"];
-        let mut display_index = 0;
-
-        while size >= 1024_f64 {
-            size /= 1024_f64;
-            display_index += 1;
-        }
-        format!("{:.2}{}", size, display[display_index])
+    pub fn send_process(&mut self) {
+        let progress = self.tracker.snapshot();
+        self.events.emit(TransferEvent::Progress(progress));
     }
 }
 
@@ -71,12 +72,11 @@ impl<W: Write> Write for ProgressWriter<W> {
         let n = self.inner.write(buf)?;
 
         if self.monitor {
-            self.bytes_send += n as u64;
+            self.tracker.add(n as u64);
         }
 
-        if self.last_send_time.elapsed() >= Duration::from_millis(500) {
-            self.send_process();
-            self.last_send_time = Instant::now();
+        if let Some(progress) = self.tracker.poll() {
+            self.events.emit(TransferEvent::Progress(progress));
         }
         Ok(n)
     }
@@ -84,3 +84,14 @@ impl<W: Write> Write for ProgressWriter<W> {
         self.inner.flush()
     }
 }
+
+pub fn format_size(mut size: f64) -> String {
+    let display = ["B", "KB", "MB", "GB", "TB"];
+    let mut display_index = 0;
+
+    while size >= 1024_f64 {
+        size /= 1024_f64;
+        display_index += 1;
+    }
+    format!("{:.2}{}", size, display[display_index])
+}
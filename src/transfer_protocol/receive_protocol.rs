@@ -1,64 +1,300 @@
 use anyhow::Context;
-use dioxus::hooks::UnboundedSender;
 use rust_i18n::t;
 use std::{
-    fs::create_dir_all,
-    io::{BufReader, Read},
+    borrow::Cow,
+    collections::HashMap,
+    fs::{create_dir_all, File, OpenOptions},
+    io::{BufReader, Read, Write},
     net::TcpStream,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use crate::transfer_protocol::TYPE_FILE;
+use crate::transfer_protocol::{
+    conflict::{free_name, ConflictAction, ConflictPolicy},
+    control::RateLimit,
+    event::{EventSink, FileStatus, TransferEvent},
+    handshake::{self, Capabilities},
+    hashing::{HashWriter, VerifySummary, HASH_LEN},
+    links,
+    manifest::{Decision, Manifest},
+    metadata::{self, FileMetadata},
+    pairing::{self, PairingCode},
+    path_codec,
+    progress::ProgressTracker,
+    progress_reader::ProgressReader,
+    sanitize::{FsRules, Sanitizer},
+    secure_stream::SecureStream,
+    temp_file::{self, temp_path},
+    CHUNK_ABORT, CHUNK_DATA, CHUNK_END, CHUNK_KEEPALIVE, RESUME_FROM, RESUME_NONE, RESUME_SKIP,
+    TYPE_ABORT, TYPE_DIR, TYPE_FILE, TYPE_HARDLINK, TYPE_KEEPALIVE, TYPE_SYMLINK,
+};
+
+/// 传输开始后超过这段时间收不到任何数据（包括保活）视为连接已断开
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 接收端启动服务时确定的选项
+#[derive(Clone, Debug, Default)]
+pub struct ReceiveOptions {
+    pub rate_limit: Arc<RateLimit>,
+    pub conflict_policy: ConflictPolicy,
+    /// 整个会话使用的固定配对码，`None` 时每次连接尝试后换一个随机码
+    pub pairing_code: Option<String>,
+}
+
+/// 续传协商时愿意提供给对端比较的已有内容
+enum Offer {
+    /// 上次中断留下的临时文件，不比对端文件大时提供；没有临时文件时提供大小相同的已有文件
+    Prefix,
+    /// 只有大小相同时提供整个文件
+    Whole,
+    Nothing,
+}
 
 pub struct ReceiveProtocol {
-    reader: BufReader<TcpStream>,
+    reader: ProgressReader<BufReader<SecureStream>>,
+    capabilities: Capabilities,
+    summary: VerifySummary,
+    rate_limit: Arc<RateLimit>,
+    conflict_policy: ConflictPolicy,
+    sanitizer: Sanitizer,
+    /// 这次传输中对端路径到内容实际所在位置的映射，硬链接只能指向这里记录的文件
+    saved: HashMap<PathBuf, PathBuf>,
 }
 impl ReceiveProtocol {
-    pub fn new(stream: TcpStream) -> Self {
-        Self {
-            reader: BufReader::new(stream),
+    pub fn new(
+        mut stream: TcpStream,
+        pairing_code: &PairingCode,
+        options: &ReceiveOptions,
+        events: Arc<dyn EventSink>,
+    ) -> anyhow::Result<Self> {
+        let peer = stream.peer_addr()?;
+        let capabilities = handshake::server(&mut stream)?;
+        let psk = pairing::server(&mut stream, &pairing_code.take())?;
+        let stream = SecureStream::accept(stream, &psk)?;
+
+        // 总量在收到清单后才知道
+        let tracker = ProgressTracker::new(peer, 0, 0);
+        Ok(Self {
+            reader: ProgressReader::new(BufReader::new(stream), tracker, events),
+            capabilities,
+            summary: VerifySummary::default(),
+            rate_limit: options.rate_limit.clone(),
+            conflict_policy: options.conflict_policy,
+            sanitizer: Sanitizer::new(FsRules::current()),
+            saved: HashMap::new(),
+        })
+    }
+
+    pub fn peer_fingerprint(&self) -> &str {
+        self.reader.get_ref().get_ref().peer_fingerprint()
+    }
+
+    fn stream(&mut self) -> &mut SecureStream {
+        self.reader.get_mut().get_mut()
+    }
+
+    /// 对端不支持清单时返回 `None`
+    pub fn receive_manifest(&mut self) -> anyhow::Result<Option<Manifest>> {
+        if !self.capabilities.contains(Capabilities::MANIFEST) {
+            return Ok(None);
         }
+        let manifest = Manifest::read_from(&mut self.reader)?;
+        self.reader
+            .set_total(manifest.file_count, manifest.total_size);
+        Ok(Some(manifest))
     }
 
+    pub fn reply_manifest(&mut self, decision: &Decision) -> anyhow::Result<()> {
+        let stream = self.stream();
+        stream.write_all(&[decision.to_byte()])?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    /// `ask` 在冲突策略为逐个询问时调用，参数是对端路径、对端文件大小和已有文件大小
     pub fn receive_file_or_dir(
         &mut self,
         save_path: &Path,
-        log: &UnboundedSender<String>,
+        events: &dyn EventSink,
+        mut ask: impl FnMut(&Path, u64, u64) -> ConflictAction,
     ) -> anyhow::Result<()> {
-        loop {
-            let Some(is_file) = self.receive_file_type() else {
-                break;
-            };
-
-            let receive_path = self.receive_file_path()?;
-
-            if is_file {
-                let save_path = save_path.join(&receive_path);
-                self.receive_file(&save_path)?;
-                log.unbounded_send(format!("{} : {receive_path:?}", t!("receive")))?;
-            } else {
-                create_dir_all(save_path.join(receive_path))?;
+        let started = Instant::now();
+        let save_path = save_path.canonicalize()?;
+        if self.capabilities.contains(Capabilities::KEEPALIVE) {
+            self.reader
+                .get_ref()
+                .get_ref()
+                .set_idle_timeout(Some(IDLE_TIMEOUT))?;
+        }
+
+        while let Some(file_type) = self.receive_file_type()? {
+            match file_type {
+                TYPE_ABORT => anyhow::bail!(t!("cancelled_by_peer")),
+                TYPE_KEEPALIVE => continue,
+                _ => {}
+            }
+
+            let sender_path = self.receive_file_path()?;
+            let receive_path = self.sanitize(&sender_path, events)?;
+            let target = safe_join(&save_path, &receive_path)?;
+
+            match file_type {
+                TYPE_FILE => {
+                    let (status, saved) =
+                        self.receive_file(&target, &receive_path, events, &mut ask)?;
+                    if let Some(saved) = saved {
+                        self.saved.insert(sender_path, saved);
+                    }
+                    self.reader.finish_file();
+                    events.emit(TransferEvent::FileDone {
+                        path: receive_path,
+                        status,
+                    });
+                }
+                TYPE_DIR => create_dir_all(target)?,
+                TYPE_SYMLINK => {
+                    let link_target = self.receive_file_path()?;
+                    let identical = || std::fs::read_link(&target).is_ok_and(|t| t == link_target);
+                    let Some(link) =
+                        self.link_destination(&target, &receive_path, events, &mut ask, identical)?
+                    else {
+                        continue;
+                    };
+                    // 无法创建的链接只跳过这一条，不中断整个传输
+                    match links::create_symlink(&save_path, &link, &link_target) {
+                        Ok(()) => events.log(format!(
+                            "{} : {receive_path:?} -> {link_target:?}",
+                            t!("link")
+                        )),
+                        Err(e) => link_failed(events, &receive_path, e),
+                    }
+                }
+                TYPE_HARDLINK => {
+                    let source_path = self.receive_file_path()?;
+                    // 源文件可能被改名、跳过或保留了用户原有的内容，只链接到这次实际收到的文件
+                    let Some(source) = self.saved.get(&source_path).cloned() else {
+                        let e = anyhow::anyhow!("{} : {source_path:?}", t!("link_source_missing"));
+                        link_failed(events, &receive_path, e);
+                        continue;
+                    };
+                    let identical = || links::same_content(&source, &target);
+                    let Some(link) =
+                        self.link_destination(&target, &receive_path, events, &mut ask, identical)?
+                    else {
+                        continue;
+                    };
+                    match links::create_hard_link(&source, &link) {
+                        Ok(()) => events.log(format!(
+                            "{} : {receive_path:?} => {source_path:?}",
+                            t!("link")
+                        )),
+                        Err(e) => link_failed(events, &receive_path, e),
+                    }
+                }
+                file_type => anyhow::bail!("{} : {file_type}", t!("incompatible_peer")),
             }
         }
+
+        self.finish(events, started.elapsed())
+    }
+
+    /// 链接条目和普通文件一样按冲突策略处理已有的条目，返回实际创建链接的路径，跳过时返回 `None`；
+    /// `identical` 判断已有的条目是否和要创建的链接相同
+    fn link_destination(
+        &self,
+        target: &Path,
+        receive_path: &Path,
+        events: &dyn EventSink,
+        ask: &mut impl FnMut(&Path, u64, u64) -> ConflictAction,
+        identical: impl FnOnce() -> bool,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let Ok(existing) = target.symlink_metadata() else {
+            return Ok(Some(target.to_path_buf()));
+        };
+        // 链接本身没有文件体，询问时大小记为 0
+        let policy = match self.conflict_policy {
+            ConflictPolicy::Ask => ask(receive_path, 0, existing.len()).into(),
+            policy => policy,
+        };
+
+        let link = resolve_link_conflict(target, policy, identical)?;
+        match &link {
+            None => events.emit(TransferEvent::FileDone {
+                path: receive_path.to_path_buf(),
+                status: match policy {
+                    ConflictPolicy::SkipIfIdentical => FileStatus::Identical,
+                    _ => FileStatus::Skipped,
+                },
+            }),
+            Some(link) if link != target => events.log(format!(
+                "{} : {receive_path:?} -> {:?}",
+                t!("renamed"),
+                link.file_name().unwrap_or_default()
+            )),
+            Some(_) => {}
+        }
+        Ok(link)
+    }
+
+    /// 转换为本机能保存的名字，名字有变化时记录下来
+    fn sanitize(&mut self, receive_path: &Path, events: &dyn EventSink) -> anyhow::Result<PathBuf> {
+        let sanitized = self.sanitizer.sanitize(receive_path);
+        if sanitized != receive_path {
+            events.log(format!(
+                "{} : {receive_path:?} -> {sanitized:?}",
+                t!("renamed")
+            ));
+        }
+        Ok(sanitized)
+    }
+
+    /// 对端发送完毕后汇总校验结果并回传
+    fn finish(&mut self, events: &dyn EventSink, elapsed: Duration) -> anyhow::Result<()> {
+        self.reader.send_process();
+        if !self.capabilities.contains(Capabilities::FILE_HASH) {
+            events.emit(TransferEvent::Finished {
+                summary: None,
+                elapsed,
+            });
+            return Ok(());
+        }
+
+        let summary = self.summary;
+        let stream = self.stream();
+        stream.write_all(&summary.to_bytes())?;
+        stream.flush()?;
+
+        events.emit(TransferEvent::Finished {
+            summary: Some(summary),
+            elapsed,
+        });
+        if summary.failed > 0 {
+            anyhow::bail!("{} : {}", t!("verify_fail_count"), summary.failed);
+        }
         Ok(())
     }
 
-    fn receive_file_type(&mut self) -> Option<bool> {
+    /// 对端发送完毕时返回 `None`
+    fn receive_file_type(&mut self) -> anyhow::Result<Option<u8>> {
         let mut type_buf = [0];
 
-        match self.reader.read(&mut type_buf) {
-            Ok(0) => None,
-            Ok(1) => Some(type_buf[0] == TYPE_FILE),
-            Ok(n) => {
-                panic!("接收文件类型失败：读取了{}字节，预期1字节", n);
-            }
-            Err(e) => {
-                panic!("接收文件类型失败: {}", e);
-            }
+        match self
+            .reader
+            .read(&mut type_buf)
+            .context("接收文件类型失败")?
+        {
+            0 => Ok(None),
+            _ => Ok(Some(type_buf[0])),
         }
     }
 
     fn receive_file_path(&mut self) -> anyhow::Result<PathBuf> {
+        if self.capabilities.contains(Capabilities::RAW_PATHS) {
+            return path_codec::read_path(&mut self.reader);
+        }
+
         let mut len_buf = [0; 2];
         self.reader.read_exact(&mut len_buf)?;
         let len = u16::from_be_bytes(len_buf);
@@ -70,18 +306,443 @@ impl ReceiveProtocol {
         Ok(PathBuf::from(path))
     }
 
-    fn receive_file(&mut self, save_path: &Path) -> anyhow::Result<()> {
+    /// 接收一个文件，保存路径上已有文件时按冲突策略处理；
+    /// 文件内容最终在本地的某个路径上（收到或已有文件相同）时一并返回这个路径
+    fn receive_file(
+        &mut self,
+        save_path: &Path,
+        receive_path: &Path,
+        events: &dyn EventSink,
+        ask: &mut impl FnMut(&Path, u64, u64) -> ConflictAction,
+    ) -> anyhow::Result<(FileStatus, Option<PathBuf>)> {
         let mut size_buf = [0; 8];
         self.reader.read_exact(&mut size_buf)?;
 
         let size = u64::from_be_bytes(size_buf);
+        events.emit(TransferEvent::FileStarted {
+            path: receive_path.to_path_buf(),
+            size,
+        });
+        self.reader.start_file(receive_path);
+        let file_metadata = if self.capabilities.contains(Capabilities::METADATA) {
+            Some(FileMetadata::read_from(&mut self.reader)?)
+        } else {
+            None
+        };
 
         create_dir_all(save_path.parent().with_context(|| "获取父路径失败")?)?;
-        let mut file = std::fs::File::create(save_path)?;
 
-        let mut limited_reader = (&mut self.reader).take(size);
+        let policy = match save_path.symlink_metadata() {
+            Err(_) => ConflictPolicy::Overwrite,
+            Ok(existing) => match self.conflict_policy {
+                ConflictPolicy::Ask => ask(receive_path, size, existing.len()).into(),
+                policy => policy,
+            },
+        };
 
-        std::io::copy(&mut limited_reader, &mut file)?;
-        Ok(())
+        let mut save_path = Cow::Borrowed(save_path);
+        let (offset, mut file, identical) = match policy {
+            ConflictPolicy::Skip => return Ok((self.skip_file(size)?, None)),
+            ConflictPolicy::Rename => {
+                save_path = Cow::Owned(free_name(&save_path));
+                events.log(format!(
+                    "{} : {receive_path:?} -> {:?}",
+                    t!("renamed"),
+                    save_path.file_name().unwrap_or_default()
+                ));
+                self.offer_resume(&save_path, size, Offer::Nothing)?
+            }
+            ConflictPolicy::SkipIfIdentical => self.offer_resume(&save_path, size, Offer::Whole)?,
+            ConflictPolicy::Overwrite | ConflictPolicy::Ask => {
+                self.offer_resume(&save_path, size, Offer::Prefix)?
+            }
+        };
+        // 对端确认整个已有文件都相同时不再发送文件体，`file` 打开的是已有文件而不是临时文件
+        if !identical {
+            // 确定要替换已有文件后再去掉它的只读属性
+            metadata::make_writable(&save_path)?;
+        }
+        let temp = temp_path(&save_path);
+        self.reader.skip(offset);
+        if offset > 0 && !identical {
+            events.log(format!(
+                "{} : {receive_path:?} ({offset}/{size})",
+                t!("resume")
+            ));
+        }
+
+        let remaining = size - offset;
+        let Some(received) = self.receive_body(&mut file, remaining)? else {
+            // 对端取消后这份不完整的文件不再有用，不留作续传
+            drop(file);
+            std::fs::remove_file(&temp)?;
+            anyhow::bail!("{} : {receive_path:?}", t!("cancelled_by_peer"));
+        };
+        if received != remaining {
+            anyhow::bail!(
+                "{} : {receive_path:?} {}/{size}",
+                t!("truncated"),
+                offset + received
+            );
+        }
+
+        let expected = self.receive_hash()?;
+        if expected.is_some_and(|expected| file.finalize() != expected) {
+            self.summary.failed += 1;
+            drop(file);
+            if !identical {
+                std::fs::remove_file(&temp)?;
+            }
+            return Ok((FileStatus::Mismatch, None));
+        }
+        if expected.is_some() {
+            self.summary.verified += 1;
+        }
+        if identical {
+            return Ok((FileStatus::Identical, Some(save_path.into_owned())));
+        }
+
+        temp_file::persist(file.get_ref(), &temp, &save_path)?;
+        drop(file);
+
+        // 权限和时间设置失败（例如目标文件系统不支持）不影响文件内容，只记录下来
+        if let Some(Err(e)) = file_metadata.map(|m| m.apply(&save_path)) {
+            events.log(format!("{} : {receive_path:?} {e}", t!("metadata_fail")));
+        }
+        Ok((FileStatus::Received, Some(save_path.into_owned())))
+    }
+
+    /// 跳过已存在的文件，对端不支持跳过时把文件体读完丢弃
+    fn skip_file(&mut self, size: u64) -> anyhow::Result<FileStatus> {
+        if self.capabilities.contains(Capabilities::SKIP) {
+            self.reader.skip(size);
+            let stream = self.stream();
+            stream.write_all(&[RESUME_SKIP])?;
+            stream.flush()?;
+            return Ok(FileStatus::Skipped);
+        }
+
+        if self.capabilities.contains(Capabilities::RESUME) {
+            let stream = self.stream();
+            stream.write_all(&[RESUME_NONE])?;
+            stream.flush()?;
+            self.reader.read_exact(&mut [0; 8])?;
+        }
+        if self.receive_body(&mut std::io::sink(), size)?.is_none() {
+            anyhow::bail!(t!("cancelled_by_peer"));
+        }
+        self.receive_hash()?;
+        Ok(FileStatus::Skipped)
+    }
+
+    /// 返回收到的字节数，对端取消时返回 `None`
+    fn receive_body(
+        &mut self,
+        file: &mut impl Write,
+        remaining: u64,
+    ) -> anyhow::Result<Option<u64>> {
+        if self.capabilities.contains(Capabilities::CHUNKED) {
+            return self.receive_chunks(file, remaining);
+        }
+        self.reader.start_monitor();
+        let copied = std::io::copy(&mut (&mut self.reader).take(remaining), file);
+        self.reader.stop_monitor();
+        Ok(Some(copied?))
+    }
+
+    /// 对端不支持校验时返回 `None`
+    fn receive_hash(&mut self) -> anyhow::Result<Option<[u8; HASH_LEN]>> {
+        if !self.capabilities.contains(Capabilities::FILE_HASH) {
+            return Ok(None);
+        }
+        let mut expected = [0; HASH_LEN];
+        self.reader.read_exact(&mut expected)?;
+        Ok(Some(expected))
+    }
+
+    /// 读取分块文件体直到结束标记，返回收到的字节数，对端取消时返回 `None`
+    fn receive_chunks(&mut self, file: &mut impl Write, limit: u64) -> anyhow::Result<Option<u64>> {
+        let mut received = 0;
+        loop {
+            let mut tag = [0];
+            self.reader.read_exact(&mut tag)?;
+
+            match tag[0] {
+                CHUNK_DATA => {
+                    let mut len = [0; 4];
+                    self.reader.read_exact(&mut len)?;
+                    let len = u32::from_be_bytes(len) as u64;
+                    if received + len > limit {
+                        anyhow::bail!("{} : {}", t!("incompatible_peer"), received + len);
+                    }
+                    // 读得慢了 TCP 窗口自然收缩，发送端随之降速
+                    self.rate_limit.consume(len as usize);
+
+                    // 只有文件数据计入进度，块标记和长度不算
+                    self.reader.start_monitor();
+                    let copied = std::io::copy(&mut (&mut self.reader).take(len), file);
+                    self.reader.stop_monitor();
+                    let copied = copied?;
+                    received += copied;
+                    if copied != len {
+                        return Ok(Some(received));
+                    }
+                }
+                CHUNK_KEEPALIVE => {}
+                CHUNK_END => return Ok(Some(received)),
+                CHUNK_ABORT => return Ok(None),
+                tag => anyhow::bail!("{} : {tag}", t!("incompatible_peer")),
+            }
+        }
+    }
+
+    /// 按 `offer` 把已有内容的长度和摘要发给对端，由对端决定从哪里继续，
+    /// 返回续传位置、从这里开始写的文件，以及整个已有文件是否都相同；
+    /// 相同时返回的是只读打开的已有文件，否则写的都是临时文件
+    fn offer_resume(
+        &mut self,
+        save_path: &Path,
+        size: u64,
+        offer: Offer,
+    ) -> anyhow::Result<(u64, HashWriter<File>, bool)> {
+        let temp = temp_path(save_path);
+        if !self.capabilities.contains(Capabilities::RESUME) {
+            return Ok((0, HashWriter::new(File::create(&temp)?), false));
+        }
+
+        let existing_len = |path: &Path| {
+            std::fs::metadata(path)
+                .ok()
+                .filter(|m| m.is_file() && m.len() > 0)
+                .map(|m| m.len())
+        };
+        let whole = || existing_len(save_path).filter(|&len| len == size);
+        // 已经完成的文件也提供给对端比较，重新发送中断的目录时不必再传一遍
+        let (existing_path, existing) = match offer {
+            Offer::Prefix => match existing_len(&temp).filter(|&len| len <= size) {
+                Some(len) => (temp.as_path(), Some(len)),
+                None => (save_path, whole()),
+            },
+            Offer::Whole => (save_path, whole()),
+            Offer::Nothing => (save_path, None),
+        };
+
+        let mut hasher = blake3::Hasher::new();
+        let stream = self.stream();
+        match existing {
+            Some(len) => {
+                hasher.update_reader(File::open(existing_path)?.take(len))?;
+                stream.write_all(&[RESUME_FROM])?;
+                stream.write_all(&len.to_be_bytes())?;
+                stream.write_all(hasher.finalize().as_bytes())?;
+            }
+            None => stream.write_all(&[RESUME_NONE])?,
+        }
+        stream.flush()?;
+
+        let mut offset = [0; 8];
+        self.reader.read_exact(&mut offset)?;
+        let offset = u64::from_be_bytes(offset);
+
+        if offset == 0 {
+            return Ok((0, HashWriter::new(File::create(&temp)?), false));
+        }
+        if Some(offset) != existing {
+            anyhow::bail!("{} : {offset}", t!("incompatible_peer"));
+        }
+
+        // 整个已有文件都相同时不会再写入，只读打开，不改动它
+        if existing_path == save_path {
+            let file = File::open(existing_path)?;
+            return Ok((offset, HashWriter::with_hasher(file, hasher), true));
+        }
+        let file = OpenOptions::new().append(true).open(existing_path)?;
+        Ok((offset, HashWriter::with_hasher(file, hasher), false))
+    }
+}
+
+/// 链接失败只影响这一条，告诉用户后继续接收
+fn link_failed(events: &dyn EventSink, receive_path: &Path, e: anyhow::Error) {
+    events.emit(TransferEvent::Error(format!(
+        "{} : {receive_path:?} {e}",
+        t!("link_fail")
+    )));
+}
+
+/// `target` 上已有条目时按 `policy` 决定链接放在哪里，跳过时返回 `None`；覆盖时先删除已有的文件或链接，
+/// 已有的是目录时报错
+fn resolve_link_conflict(
+    target: &Path,
+    policy: ConflictPolicy,
+    identical: impl FnOnce() -> bool,
+) -> anyhow::Result<Option<PathBuf>> {
+    match policy {
+        ConflictPolicy::Skip => Ok(None),
+        ConflictPolicy::SkipIfIdentical if identical() => Ok(None),
+        ConflictPolicy::Rename => Ok(Some(free_name(target))),
+        ConflictPolicy::SkipIfIdentical | ConflictPolicy::Overwrite | ConflictPolicy::Ask => {
+            metadata::make_writable(target)?;
+            links::remove_existing(target)?;
+            Ok(Some(target.to_path_buf()))
+        }
+    }
+}
+
+/// 把对端发来的相对路径拼到保存目录下，拒绝绝对路径、盘符、`..` 以及指向保存目录之外的符号链接
+fn safe_join(save_path: &Path, receive_path: &Path) -> anyhow::Result<PathBuf> {
+    let unsafe_path = || format!("{} : {receive_path:?}", t!("unsafe_path"));
+
+    let mut target = save_path.to_path_buf();
+    // 保存目录本身可能经过符号链接或是相对路径，和链接目标比较前先解析
+    let mut canonical_root = None;
+    for component in receive_path.components() {
+        match component {
+            Component::Normal(name) => {
+                target.push(name);
+
+                // 已存在的每一级都不能借符号链接跳出保存目录
+                let is_symlink = target
+                    .symlink_metadata()
+                    .is_ok_and(|m| m.file_type().is_symlink());
+                if is_symlink {
+                    let root = match &canonical_root {
+                        Some(root) => root,
+                        None => canonical_root.insert(save_path.canonicalize()?),
+                    };
+                    if !target.canonicalize()?.starts_with(root) {
+                        anyhow::bail!(unsafe_path());
+                    }
+                }
+            }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                anyhow::bail!(unsafe_path());
+            }
+        }
+    }
+
+    if target == save_path {
+        anyhow::bail!(unsafe_path());
+    }
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(save_path: &Path, receive_path: &str) -> anyhow::Result<PathBuf> {
+        safe_join(save_path, Path::new(receive_path))
+    }
+
+    #[test]
+    fn joins_relative_paths() {
+        let save = Path::new("save");
+        assert_eq!(join(save, "a.txt").unwrap(), save.join("a.txt"));
+        assert_eq!(join(save, "dir/./a.txt").unwrap(), save.join("dir/a.txt"));
+    }
+
+    #[test]
+    fn rejects_parent_dir() {
+        let save = Path::new("save");
+        assert!(join(save, "..").is_err());
+        assert!(join(save, "../a.txt").is_err());
+        assert!(join(save, "dir/../../a.txt").is_err());
+        assert!(join(save, "dir/..").is_err());
+    }
+
+    #[test]
+    fn rejects_absolute_and_empty_paths() {
+        let save = Path::new("save");
+        assert!(join(save, "/etc/passwd").is_err());
+        assert!(join(save, "").is_err());
+        assert!(join(save, ".").is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn rejects_drive_and_unc_prefixes() {
+        let save = Path::new("save");
+        assert!(join(save, r"C:\Windows\win.ini").is_err());
+        assert!(join(save, "C:win.ini").is_err());
+        assert!(join(save, r"\\server\share\a.txt").is_err());
+        assert!(join(save, r"\\?\C:\a.txt").is_err());
+        assert!(join(save, r"\a.txt").is_err());
+    }
+
+    /// 在 Unix 上盘符和反斜杠只是普通字符，拼出来的仍在保存目录下
+    #[cfg(unix)]
+    #[test]
+    fn drive_and_unc_prefixes_stay_inside() {
+        let save = Path::new("save");
+        for name in [r"C:\Windows\win.ini", r"\\server\share\a.txt"] {
+            let joined = join(save, name).unwrap();
+            assert_eq!(joined.parent(), Some(save));
+        }
+    }
+
+    #[test]
+    fn link_conflict_follows_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("notes.txt");
+        std::fs::write(&existing, "local edits").unwrap();
+
+        // 跳过时同名的链接条目不能动已有文件
+        assert_eq!(
+            resolve_link_conflict(&existing, ConflictPolicy::Skip, || false).unwrap(),
+            None
+        );
+        assert_eq!(
+            resolve_link_conflict(&existing, ConflictPolicy::SkipIfIdentical, || true).unwrap(),
+            None
+        );
+        assert_eq!(
+            resolve_link_conflict(&existing, ConflictPolicy::Rename, || false).unwrap(),
+            Some(dir.path().join("notes (1).txt"))
+        );
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "local edits");
+
+        assert_eq!(
+            resolve_link_conflict(&existing, ConflictPolicy::Overwrite, || false).unwrap(),
+            Some(existing.clone())
+        );
+        assert!(existing.symlink_metadata().is_err());
+    }
+
+    #[test]
+    fn link_conflict_never_removes_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("sub");
+        create_dir_all(existing.join("inner")).unwrap();
+
+        assert!(resolve_link_conflict(&existing, ConflictPolicy::Overwrite, || false).is_err());
+        assert!(existing.join("inner").is_dir());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinked_parent_outside_save_dir() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        let save = dir.path().join("save");
+        let outside = dir.path().join("outside");
+        create_dir_all(save.join("inner")).unwrap();
+        create_dir_all(&outside).unwrap();
+        symlink(&outside, save.join("escape")).unwrap();
+        symlink(save.join("inner"), save.join("alias")).unwrap();
+
+        assert!(join(&save, "escape/a.txt").is_err());
+        assert!(join(&save, "escape").is_err());
+        assert_eq!(
+            join(&save, "alias/a.txt").unwrap(),
+            save.join("alias/a.txt")
+        );
+
+        // 通过符号链接进入保存目录时，指向目录内的链接仍然允许
+        let linked_save = dir.path().join("linked-save");
+        symlink(&save, &linked_save).unwrap();
+        assert!(join(&linked_save, "alias/a.txt").is_ok());
+        assert!(join(&linked_save, "escape/a.txt").is_err());
     }
 }
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::Duration};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Send,
+    Receive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Completed,
+    Rejected,
+    Cancelled,
+    Failed,
+}
+
+impl Outcome {
+    pub const ALL: [Outcome; 4] = [
+        Outcome::Completed,
+        Outcome::Rejected,
+        Outcome::Cancelled,
+        Outcome::Failed,
+    ];
+}
+
+/// 一次发送或一个接收连接的结果，结束时通过 `TransferEvent::Record` 发出，调用方据此记录历史
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransferRecord {
+    /// 开始时间
+    pub time: DateTime<Local>,
+    pub direction: Direction,
+    /// 对端地址，发送时连接前为输入的目标地址
+    pub peer: String,
+    /// 对端的设备名，只有接收时能从清单得知
+    #[serde(default)]
+    pub peer_name: String,
+    /// 发送的文件或文件夹，或者接收时的保存目录
+    pub local_path: PathBuf,
+    /// 清单里的顶层文件和文件夹名
+    #[serde(default)]
+    pub names: Vec<String>,
+    pub files: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    pub outcome: Outcome,
+    pub error: Option<String>,
+}
+
+impl TransferRecord {
+    pub fn new(direction: Direction, peer: String, local_path: PathBuf) -> Self {
+        Self {
+            time: Local::now(),
+            direction,
+            peer,
+            peer_name: String::new(),
+            local_path,
+            names: Vec::new(),
+            files: 0,
+            bytes: 0,
+            elapsed: Duration::ZERO,
+            outcome: Outcome::Failed,
+            error: None,
+        }
+    }
+
+    pub fn finish(&mut self, outcome: Outcome, error: Option<&anyhow::Error>, elapsed: Duration) {
+        self.outcome = outcome;
+        self.error = error.map(|e| format!("{e:#}"));
+        self.elapsed = elapsed;
+    }
+
+    /// 总字节数除以用时，单位为字节每秒，未完成的传输为 0
+    pub fn average_speed(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if self.outcome == Outcome::Completed && secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    path::{Component, Path, PathBuf},
+};
+use unicode_normalization::UnicodeNormalization;
+
+/// 设为 windows、macos 或 unix 时按对应平台的规则处理文件名，用于在其他平台上模拟
+const RULES_ENV: &str = "TRANSFER_FS_RULES";
+
+const WINDOWS_RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+const WINDOWS_ILLEGAL: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// 接收端文件系统的命名规则
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FsRules {
+    /// 不允许保留设备名、非法字符和结尾的点或空格，不区分大小写
+    Windows,
+    /// 不区分大小写
+    MacOs,
+    Unix,
+}
+
+impl FsRules {
+    /// 本机的规则，可以用环境变量 `TRANSFER_FS_RULES` 覆盖
+    pub fn current() -> Self {
+        match std::env::var(RULES_ENV).as_deref() {
+            Ok("windows") => Self::Windows,
+            Ok("macos") => Self::MacOs,
+            Ok("unix") => Self::Unix,
+            _ if cfg!(windows) => Self::Windows,
+            _ if cfg!(target_os = "macos") => Self::MacOs,
+            _ => Self::Unix,
+        }
+    }
+
+    fn case_insensitive(self) -> bool {
+        self != Self::Unix
+    }
+}
+
+/// 把对端发来的相对路径转换为本机能保存的名字：统一为 NFC，按目标平台替换非法名字，
+/// 同一次传输中清理后重名（包括只有大小写不同）的条目加上编号区分
+pub struct Sanitizer {
+    rules: FsRules,
+    /// 对端路径的每一级前缀到实际使用的路径，目录改名后其下的条目跟着改
+    assigned: HashMap<PathBuf, PathBuf>,
+    /// 已经使用的路径，不区分大小写时按小写记录
+    taken: HashSet<OsString>,
+}
+
+impl Sanitizer {
+    pub fn new(rules: FsRules) -> Self {
+        Self {
+            rules,
+            assigned: HashMap::new(),
+            taken: HashSet::new(),
+        }
+    }
+
+    /// `..` 等非普通部分原样保留，交给 `safe_join` 拒绝
+    pub fn sanitize(&mut self, path: &Path) -> PathBuf {
+        let mut incoming = PathBuf::new();
+        let mut output = PathBuf::new();
+
+        for component in path.components() {
+            incoming.push(component);
+            if let Some(assigned) = self.assigned.get(&incoming) {
+                output = assigned.clone();
+                continue;
+            }
+
+            match component {
+                Component::Normal(name) => {
+                    let name = self.clean_name(name);
+                    output = self.claim(&output, &name);
+                }
+                other => output.push(other),
+            }
+            self.assigned.insert(incoming.clone(), output.clone());
+        }
+        output
+    }
+
+    fn clean_name(&self, name: &OsStr) -> OsString {
+        let name = match name.to_str() {
+            Some(name) => name.nfc().collect::<String>(),
+            // 非 UTF-8 的名字只会出现在 Unix 上，只有模拟 Windows 时才需要转换
+            None if self.rules == FsRules::Windows => name.to_string_lossy().nfc().collect(),
+            None => return name.to_os_string(),
+        };
+
+        match self.rules {
+            FsRules::Windows => windows_name(&name).into(),
+            FsRules::MacOs | FsRules::Unix => name.into(),
+        }
+    }
+
+    /// 在 `parent` 下占用 `name`，已被占用时依次尝试 "name (1).ext"、"name (2).ext"
+    fn claim(&mut self, parent: &Path, name: &OsStr) -> PathBuf {
+        let mut candidate = parent.join(name);
+        let mut n = 1;
+        loop {
+            let key = self.key(&candidate);
+            if self.taken.insert(key) {
+                return candidate;
+            }
+            candidate = parent.join(numbered(name, n));
+            n += 1;
+        }
+    }
+
+    fn key(&self, path: &Path) -> OsString {
+        if self.rules.case_insensitive() {
+            path.to_string_lossy().to_lowercase().into()
+        } else {
+            path.as_os_str().to_os_string()
+        }
+    }
+}
+
+/// 非法字符和控制字符替换为 `_`，结尾的点和空格会被 Windows 丢掉，同样替换，保留设备名前加 `_`
+fn windows_name(name: &str) -> String {
+    let mut name: String = name
+        .chars()
+        .map(|c| {
+            if WINDOWS_ILLEGAL.contains(&c) || c.is_ascii_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    let kept = name.trim_end_matches(['.', ' ']).len();
+    let trailing = name.len() - kept;
+    name.replace_range(kept.., &"_".repeat(trailing));
+
+    // "aux.txt"、"nul .tar.gz" 这样去掉扩展名后是设备名的也不行
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if WINDOWS_RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// 编号加在最后一个扩展名之前，以点开头的隐藏文件名整体视为主名
+pub fn numbered(name: &OsStr, n: usize) -> OsString {
+    let name = name.to_string_lossy();
+    match name.rfind('.').filter(|&dot| dot > 0) {
+        Some(dot) => format!("{} ({n}){}", &name[..dot], &name[dot..]).into(),
+        None => format!("{name} ({n})").into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(sanitizer: &mut Sanitizer, incoming: &str, expected: &str) {
+        assert_eq!(sanitizer.sanitize(Path::new(incoming)), Path::new(expected));
+    }
+
+    #[test]
+    fn windows_reserved_names_get_a_prefix() {
+        let mut sanitizer = Sanitizer::new(FsRules::Windows);
+        check(&mut sanitizer, "aux.txt", "_aux.txt");
+        check(&mut sanitizer, "CON", "_CON");
+        check(&mut sanitizer, "nul .tar.gz", "_nul .tar.gz");
+        check(&mut sanitizer, "auxiliary.txt", "auxiliary.txt");
+    }
+
+    #[test]
+    fn windows_illegal_characters_are_replaced() {
+        let mut sanitizer = Sanitizer::new(FsRules::Windows);
+        check(&mut sanitizer, "file:name", "file_name");
+        check(&mut sanitizer, "a<b>c|d?e*", "a_b_c_d_e_");
+        check(&mut sanitizer, "tab\there", "tab_here");
+    }
+
+    #[test]
+    fn windows_trailing_dots_and_spaces_are_replaced() {
+        let mut sanitizer = Sanitizer::new(FsRules::Windows);
+        check(&mut sanitizer, "name.", "name_");
+        check(&mut sanitizer, "notes. .", "notes___");
+        check(&mut sanitizer, "dir /file", "dir_/file");
+    }
+
+    #[test]
+    fn names_are_normalised_to_nfc() {
+        let mut sanitizer = Sanitizer::new(FsRules::Windows);
+        check(&mut sanitizer, "cafe\u{301}.txt", "caf\u{e9}.txt");
+        // 已经是 NFC 的同名条目视为重名
+        check(&mut sanitizer, "caf\u{e9}.txt", "caf\u{e9} (1).txt");
+    }
+
+    #[test]
+    fn case_insensitive_collisions_are_numbered() {
+        let mut sanitizer = Sanitizer::new(FsRules::Windows);
+        check(&mut sanitizer, "Readme.md", "Readme.md");
+        check(&mut sanitizer, "README.md", "README (1).md");
+        check(&mut sanitizer, "readme.MD", "readme (2).MD");
+
+        // 目录改名后其下的条目跟着改
+        check(&mut sanitizer, "Docs", "Docs");
+        check(&mut sanitizer, "docs", "docs (1)");
+        check(&mut sanitizer, "docs/a.txt", "docs (1)/a.txt");
+    }
+
+    #[test]
+    fn unix_keeps_case_and_characters() {
+        let mut sanitizer = Sanitizer::new(FsRules::Unix);
+        check(&mut sanitizer, "file:name", "file:name");
+        check(&mut sanitizer, "README.md", "README.md");
+        check(&mut sanitizer, "readme.md", "readme.md");
+    }
+}
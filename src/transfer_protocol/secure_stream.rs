@@ -0,0 +1,261 @@
+use crate::transfer_protocol::pairing::SESSION_KEY_LEN;
+use anyhow::Context;
+use rust_i18n::t;
+use std::{
+    fs,
+    io::{ErrorKind, Read, Write},
+    net::{Shutdown, TcpStream},
+    path::PathBuf,
+    sync::OnceLock,
+    time::Duration,
+};
+
+/// 第三条握手消息混入配对得到的 PSK，配对码不一致时握手无法完成
+const NOISE_PARAMS: &str = "Noise_XXpsk3_25519_ChaChaPoly_BLAKE2s";
+/// Noise 单条消息的上限，其中 16 字节是认证标签
+const MAX_MESSAGE_LEN: usize = 65535;
+const TAG_LEN: usize = 16;
+const MAX_PLAINTEXT_LEN: usize = MAX_MESSAGE_LEN - TAG_LEN;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 在 `TcpStream` 之上做 Noise XXpsk3 握手后得到的加密流，每个帧是 u16 长度加密文
+pub struct SecureStream {
+    stream: TcpStream,
+    transport: snow::TransportState,
+    read_buf: Vec<u8>,
+    read_pos: usize,
+    write_buf: Vec<u8>,
+    message: Vec<u8>,
+    peer_fingerprint: String,
+}
+
+impl SecureStream {
+    /// 发送端作为发起方完成握手
+    pub fn connect(mut stream: TcpStream, psk: &[u8; SESSION_KEY_LEN]) -> anyhow::Result<Self> {
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+
+        let identity = local_identity();
+        let mut noise = snow::Builder::new(NOISE_PARAMS.parse()?)
+            .local_private_key(&identity.private)
+            .psk(3, psk)
+            .build_initiator()?;
+        let mut message = vec![0; MAX_MESSAGE_LEN];
+        let mut payload = vec![0; MAX_MESSAGE_LEN];
+
+        // -> e
+        let len = noise.write_message(&[], &mut message)?;
+        write_frame(&mut stream, &message[..len])?;
+        // <- e, ee, s, es
+        let frame = read_frame(&mut stream)?.context(t!("handshake_fail"))?;
+        noise.read_message(&frame, &mut payload)?;
+        // -> s, se
+        let len = noise.write_message(&[], &mut message)?;
+        write_frame(&mut stream, &message[..len])?;
+
+        stream.set_read_timeout(None)?;
+        Self::from_handshake(stream, noise)
+    }
+
+    /// 接收端作为响应方完成握手
+    pub fn accept(mut stream: TcpStream, psk: &[u8; SESSION_KEY_LEN]) -> anyhow::Result<Self> {
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+
+        let identity = local_identity();
+        let mut noise = snow::Builder::new(NOISE_PARAMS.parse()?)
+            .local_private_key(&identity.private)
+            .psk(3, psk)
+            .build_responder()?;
+        let mut message = vec![0; MAX_MESSAGE_LEN];
+        let mut payload = vec![0; MAX_MESSAGE_LEN];
+
+        let frame = read_frame(&mut stream)?.context(t!("handshake_fail"))?;
+        noise.read_message(&frame, &mut payload)?;
+        let len = noise.write_message(&[], &mut message)?;
+        write_frame(&mut stream, &message[..len])?;
+        let frame = read_frame(&mut stream)?.context(t!("handshake_fail"))?;
+        noise.read_message(&frame, &mut payload)?;
+
+        stream.set_read_timeout(None)?;
+        Self::from_handshake(stream, noise)
+    }
+
+    fn from_handshake(stream: TcpStream, noise: snow::HandshakeState) -> anyhow::Result<Self> {
+        let peer_fingerprint =
+            fingerprint(noise.get_remote_static().context(t!("handshake_fail"))?);
+
+        Ok(Self {
+            stream,
+            transport: noise.into_transport_mode()?,
+            read_buf: Vec::new(),
+            read_pos: 0,
+            write_buf: Vec::with_capacity(MAX_PLAINTEXT_LEN),
+            message: vec![0; MAX_MESSAGE_LEN],
+            peer_fingerprint,
+        })
+    }
+
+    pub fn peer_fingerprint(&self) -> &str {
+        &self.peer_fingerprint
+    }
+
+    /// 发出缓冲中的数据后关闭写方向，对端读到 EOF
+    pub fn shutdown_write(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        self.stream.shutdown(Shutdown::Write)
+    }
+
+    /// 读超时，超过这段时间收不到任何帧时读操作返回错误
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    /// 等待对端数据到达，超时返回 `false`，调用方借此在阻塞读之前检查取消
+    pub fn poll_readable(&self, timeout: Duration) -> std::io::Result<bool> {
+        if self.read_pos < self.read_buf.len() {
+            return Ok(true);
+        }
+
+        self.stream.set_read_timeout(Some(timeout))?;
+        let ready = match self.stream.peek(&mut [0]) {
+            Ok(_) => Ok(true),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(false),
+            Err(e) => Err(e),
+        };
+        self.stream.set_read_timeout(None)?;
+        ready
+    }
+
+    fn send_buffered(&mut self) -> std::io::Result<()> {
+        if self.write_buf.is_empty() {
+            return Ok(());
+        }
+
+        let len = self
+            .transport
+            .write_message(&self.write_buf, &mut self.message)
+            .map_err(std::io::Error::other)?;
+        write_frame(&mut self.stream, &self.message[..len])?;
+        self.write_buf.clear();
+        Ok(())
+    }
+}
+
+impl Read for SecureStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.read_pos == self.read_buf.len() {
+            let Some(frame) = read_frame(&mut self.stream)? else {
+                return Ok(0);
+            };
+
+            self.read_buf.resize(MAX_MESSAGE_LEN, 0);
+            let len = self
+                .transport
+                .read_message(&frame, &mut self.read_buf)
+                .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+            self.read_buf.truncate(len);
+            self.read_pos = 0;
+        }
+
+        let n = buf.len().min(self.read_buf.len() - self.read_pos);
+        buf[..n].copy_from_slice(&self.read_buf[self.read_pos..self.read_pos + n]);
+        self.read_pos += n;
+        Ok(n)
+    }
+}
+
+impl Write for SecureStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.write_buf.len() == MAX_PLAINTEXT_LEN {
+            self.send_buffered()?;
+        }
+
+        let n = buf.len().min(MAX_PLAINTEXT_LEN - self.write_buf.len());
+        self.write_buf.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_buffered()?;
+        self.stream.flush()
+    }
+}
+
+/// 读一个帧，帧边界上遇到 EOF 时返回 `None`
+fn read_frame(stream: &mut TcpStream) -> std::io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 2];
+    match stream.read(&mut len[..1])? {
+        0 => return Ok(None),
+        _ => stream.read_exact(&mut len[1..])?,
+    }
+
+    let mut frame = vec![0; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+fn write_frame(stream: &mut TcpStream, frame: &[u8]) -> std::io::Result<()> {
+    let mut buf = Vec::with_capacity(frame.len() + 2);
+    buf.extend_from_slice(&(frame.len() as u16).to_be_bytes());
+    buf.extend_from_slice(frame);
+    stream.write_all(&buf)
+}
+
+/// 公钥的 BLAKE3 摘要前 8 字节，按两字节一组显示
+fn fingerprint(public_key: &[u8]) -> String {
+    blake3::hash(public_key).as_bytes()[..8]
+        .chunks(2)
+        .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+pub fn local_fingerprint() -> String {
+    fingerprint(&local_identity().public)
+}
+
+fn local_identity() -> &'static snow::Keypair {
+    static IDENTITY: OnceLock<snow::Keypair> = OnceLock::new();
+    IDENTITY.get_or_init(load_identity)
+}
+
+/// 本机的长期身份密钥，首次使用时生成并保存在配置目录，无法保存时只在本次运行中使用
+fn load_identity() -> snow::Keypair {
+    let path = identity_path();
+    if let Some(bytes) = path.as_ref().and_then(|p| fs::read(p).ok()) {
+        if bytes.len() == 64 {
+            return snow::Keypair {
+                private: bytes[..32].to_vec(),
+                public: bytes[32..].to_vec(),
+            };
+        }
+    }
+
+    let identity = snow::Builder::new(NOISE_PARAMS.parse().expect("有效的 Noise 参数"))
+        .generate_keypair()
+        .expect("生成密钥失败");
+    if let Some(path) = path {
+        _ = save_identity(&path, &identity);
+    }
+    identity
+}
+
+fn identity_path() -> Option<PathBuf> {
+    Some(
+        dirs::config_dir()?
+            .join("transfer-dioxus")
+            .join("identity.key"),
+    )
+}
+
+fn save_identity(path: &PathBuf, identity: &snow::Keypair) -> std::io::Result<()> {
+    fs::create_dir_all(path.parent().unwrap_or(path))?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    file.write_all(&identity.private)?;
+    file.write_all(&identity.public)?;
+    Ok(())
+}
@@ -1,58 +1,168 @@
-use crate::transfer_protocol::{progress_writer::ProgressWriter, TYPE_DIR, TYPE_FILE};
+use crate::transfer_protocol::{
+    control::TransferControl,
+    event::{EventSink, FileStatus, TransferEvent},
+    handshake::{self, Capabilities},
+    hashing::{HashReader, VerifySummary, HASH_LEN},
+    links::HardLinks,
+    manifest::{Decision, Manifest},
+    metadata::FileMetadata,
+    pairing, path_codec,
+    progress::ProgressTracker,
+    progress_writer::ProgressWriter,
+    secure_stream::SecureStream,
+    CHUNK_ABORT, CHUNK_DATA, CHUNK_END, CHUNK_KEEPALIVE, CHUNK_SIZE, RESUME_FROM, RESUME_NONE,
+    RESUME_SKIP, TYPE_ABORT, TYPE_DIR, TYPE_FILE, TYPE_HARDLINK, TYPE_KEEPALIVE, TYPE_SYMLINK,
+};
 use anyhow::Context;
-use dioxus::hooks::UnboundedSender;
 use rust_i18n::t;
 use std::{
-    io::{BufWriter, Write},
+    fs::FileType,
+    io::{BufWriter, Read, Write},
     net::TcpStream,
     path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
 };
+use walkdir::DirEntry;
+
+type MonitorStream = ProgressWriter<SecureStream>;
 
-type MonitorStream = ProgressWriter<TcpStream>;
+/// 等待对端回复时检查取消的间隔
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// 暂停期间发送保活标记的间隔，需明显短于接收端的空闲超时
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
 
 pub struct SendProtocol {
     writer: BufWriter<MonitorStream>,
+    capabilities: Capabilities,
+    control: Arc<TransferControl>,
+    hard_links: HardLinks,
 }
 
 impl SendProtocol {
     pub fn new(
-        stream: TcpStream,
+        mut stream: TcpStream,
+        pairing_code: &str,
+        file_count: u64,
         total_size: u64,
-        progress_tx: UnboundedSender<(f64, String)>,
-    ) -> Self {
-        let monitor = MonitorStream::new(stream, total_size, progress_tx);
-        Self {
+        events: Arc<dyn EventSink>,
+        control: Arc<TransferControl>,
+    ) -> anyhow::Result<Self> {
+        let peer = stream.peer_addr()?;
+        let capabilities = handshake::client(&mut stream)?;
+        let psk = pairing::client(&mut stream, pairing_code)?;
+        let stream = SecureStream::connect(stream, &psk)?;
+
+        let monitor = MonitorStream::new(
+            stream,
+            ProgressTracker::new(peer, file_count, total_size),
+            events,
+        );
+        Ok(Self {
             writer: BufWriter::new(monitor),
+            capabilities,
+            control,
+            hard_links: HardLinks::default(),
+        })
+    }
+
+    /// 发送清单并等待接收端确认，对端不支持清单时视为直接接受
+    pub fn send_manifest(&mut self, manifest: &Manifest) -> anyhow::Result<bool> {
+        if !self.capabilities.contains(Capabilities::MANIFEST) {
+            return Ok(true);
+        }
+
+        manifest.write_to(&mut self.writer)?;
+        self.writer.flush()?;
+
+        // 接收端确认前可能等待很久，期间仍要响应取消
+        while !self
+            .writer
+            .get_mut()
+            .get_mut()
+            .poll_readable(CANCEL_POLL_INTERVAL)?
+        {
+            if self.control.is_cancelled() {
+                self.abort()?;
+            }
         }
+
+        let mut decision = [0];
+        self.writer.get_mut().get_mut().read_exact(&mut decision)?;
+        Ok(Decision::is_accepted(decision[0]))
     }
-    pub fn flush(&mut self) -> anyhow::Result<()> {
+
+    /// 发送完所有条目后调用，对端支持校验时等待其回传校验结果
+    pub fn finish(&mut self) -> anyhow::Result<Option<VerifySummary>> {
         self.writer.flush()?;
-        Ok(())
+
+        if !self.capabilities.contains(Capabilities::FILE_HASH) {
+            return Ok(None);
+        }
+
+        let stream = self.writer.get_mut().get_mut();
+        stream.shutdown_write()?;
+
+        let mut summary = [0; 16];
+        stream
+            .read_exact(&mut summary)
+            .with_context(|| t!("no_verify_summary"))?;
+        Ok(Some(VerifySummary::from_bytes(summary)))
+    }
+    pub fn peer_fingerprint(&self) -> &str {
+        self.writer.get_ref().get_ref().peer_fingerprint()
     }
     pub fn get_ref(&self) -> &MonitorStream {
         self.writer.get_ref()
     }
+    pub fn get_mut(&mut self) -> &mut MonitorStream {
+        self.writer.get_mut()
+    }
+    fn events(&self) -> &dyn EventSink {
+        self.writer.get_ref().events()
+    }
 
-    pub fn send_file_or_dir(
-        &mut self,
-        path: &Path,
-        root_dir: &Path,
-        log_tx: &UnboundedSender<String>,
-    ) -> anyhow::Result<()> {
-        self.send_file_type(path)?;
+    pub fn send_file_or_dir(&mut self, entry: &DirEntry, root_dir: &Path) -> anyhow::Result<()> {
+        self.wait_while_paused(TYPE_KEEPALIVE)?;
+        if self.control.is_cancelled() {
+            self.abort()?;
+        }
 
+        let path = entry.path();
         let relative_path = path.strip_prefix(root_dir)?;
-        self.send_path_name(&relative_path)?;
+        // 跟随链接遍历时这里已是链接目标的类型，只有保留链接时才会遇到符号链接
+        let file_type = entry.file_type();
 
-        if path.is_file() {
-            log_tx.unbounded_send(format!("{} : {relative_path:?}", t!("send")))?;
-            self.send_file(path)?;
+        if file_type.is_symlink() {
+            return self.send_symlink(path, relative_path);
+        }
+        if !file_type.is_file() && !file_type.is_dir() {
+            self.events()
+                .log(format!("{} : {relative_path:?}", t!("skipped")));
+            return Ok(());
+        }
+        if file_type.is_file() && self.capabilities.contains(Capabilities::LINKS) {
+            if let Some(source) = self.hard_links.check(&entry.metadata()?, relative_path) {
+                self.writer.write_all(&[TYPE_HARDLINK])?;
+                self.send_path_name(relative_path)?;
+                self.send_path_name(&source)?;
+                self.events()
+                    .log(format!("{} : {relative_path:?} => {source:?}", t!("link")));
+                return Ok(());
+            }
+        }
+
+        self.send_file_type(file_type)?;
+        self.send_path_name(relative_path)?;
+
+        if file_type.is_file() {
+            self.send_file(path, relative_path)?;
         }
         Ok(())
     }
 
-    fn send_file_type(&mut self, path: &Path) -> anyhow::Result<()> {
-        if path.is_file() {
+    fn send_file_type(&mut self, file_type: FileType) -> anyhow::Result<()> {
+        if file_type.is_file() {
             self.writer.write_all(&[TYPE_FILE])?;
         } else {
             self.writer.write_all(&[TYPE_DIR])?;
@@ -60,32 +170,238 @@ impl SendProtocol {
         Ok(())
     }
 
+    /// 符号链接只发送链接目标本身，对端不支持时跳过
+    fn send_symlink(&mut self, path: &Path, relative_path: &Path) -> anyhow::Result<()> {
+        if !self.capabilities.contains(Capabilities::LINKS) {
+            self.events()
+                .log(format!("{} : {relative_path:?}", t!("skipped")));
+            return Ok(());
+        }
+
+        let target = std::fs::read_link(path)?;
+        self.writer.write_all(&[TYPE_SYMLINK])?;
+        self.send_path_name(relative_path)?;
+        self.send_path_name(&target)?;
+        self.events()
+            .log(format!("{} : {relative_path:?} -> {target:?}", t!("link")));
+        Ok(())
+    }
+
     fn send_path_name(&mut self, path_name: &Path) -> anyhow::Result<()> {
+        if self.capabilities.contains(Capabilities::RAW_PATHS) {
+            path_codec::write_path(&mut self.writer, path_name)?;
+            return Ok(());
+        }
+
         let path_name = path_name
             .to_str()
             .with_context(|| format!("{path_name:?}不是有效的unicode"))?
             .as_bytes();
-        let path_name_len: [u8; 2] = (path_name.len() as u16).to_be_bytes();
+        let path_name_len: [u8; 2] = u16::try_from(path_name.len())
+            .with_context(|| format!("{} : {}", t!("path_too_long"), path_name.len()))?
+            .to_be_bytes();
 
         self.writer.write_all(&path_name_len)?;
         self.writer.write_all(path_name)?;
         Ok(())
     }
 
-    fn send_file(&mut self, file_path: &Path) -> anyhow::Result<()> {
+    fn send_file(&mut self, file_path: &Path, relative_path: &Path) -> anyhow::Result<()> {
         let metadata = file_path.metadata()?;
-        let file_size: [u8; 8] = metadata.len().to_be_bytes();
+        let size = metadata.len();
+        self.events().emit(TransferEvent::FileStarted {
+            path: relative_path.to_path_buf(),
+            size,
+        });
+        self.writer.get_mut().start_file(relative_path);
+        self.writer.write_all(&size.to_be_bytes())?;
+        if self.capabilities.contains(Capabilities::METADATA) {
+            FileMetadata::from_metadata(&metadata).write_to(&mut self.writer)?;
+        }
 
-        self.writer.write_all(&file_size)?;
+        let mut file = HashReader::new(std::fs::File::open(file_path)?);
 
-        let mut file = std::fs::File::open(file_path)?;
+        let offset = if self.capabilities.contains(Capabilities::RESUME) {
+            match self.negotiate_resume(&mut file, size)? {
+                Some(offset) => offset,
+                None => {
+                    self.events().emit(TransferEvent::FileDone {
+                        path: relative_path.to_path_buf(),
+                        status: FileStatus::SkippedByPeer,
+                    });
+                    self.writer.get_mut().skip(size);
+                    self.writer.get_mut().finish_file();
+                    return Ok(());
+                }
+            }
+        } else {
+            0
+        };
+        if offset > 0 {
+            self.events().log(format!(
+                "{} : {relative_path:?} ({offset}/{size})",
+                t!("resume")
+            ));
+            self.writer.get_mut().skip(offset);
+        }
+
+        let remaining = size - offset;
+        let mut file = file.take(remaining);
 
         self.writer.flush()?;
-        self.writer.get_mut().start_monitor();
-        std::io::copy(&mut file, &mut self.writer)?;
+        let copied = if self.capabilities.contains(Capabilities::CHUNKED) {
+            self.send_chunks(&mut file)
+        } else {
+            self.writer.get_mut().start_monitor();
+            let copied = self.send_raw(&mut file);
+            // 缓冲里剩下的文件数据写出去之后再停止计数
+            let flushed = self.writer.flush();
+            self.writer.get_mut().stop_monitor();
+            copied.and_then(|copied| flushed.map(|()| copied)).map_err(Into::into)
+        };
+        let copied = copied?;
         self.writer.flush()?;
-        self.writer.get_mut().stop_monitor();
 
+        if copied != remaining {
+            anyhow::bail!("{} : {file_path:?}", t!("file_changed"));
+        }
+
+        if self.capabilities.contains(Capabilities::FILE_HASH) {
+            self.writer.write_all(&file.into_inner().finalize())?;
+        }
+
+        self.events().emit(TransferEvent::FileDone {
+            path: relative_path.to_path_buf(),
+            status: FileStatus::Sent,
+        });
+        self.writer.get_mut().finish_file();
         Ok(())
     }
+
+    /// 逐块发送文件体，每块之前检查是否被取消，返回实际发送的字节数
+    fn send_chunks(&mut self, file: &mut impl Read) -> anyhow::Result<u64> {
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut sent = 0;
+        loop {
+            self.wait_while_paused(CHUNK_KEEPALIVE)?;
+            if self.control.is_cancelled() {
+                self.writer.write_all(&[CHUNK_ABORT])?;
+                self.writer.flush()?;
+                anyhow::bail!(t!("cancelled"));
+            }
+
+            let len = self.control.rate_limit().slice_len(CHUNK_SIZE);
+            let n = file.read(&mut buf[..len])?;
+            if n == 0 {
+                break;
+            }
+            self.control.rate_limit().consume(n);
+            self.writer.write_all(&[CHUNK_DATA])?;
+            self.writer.write_all(&(n as u32).to_be_bytes())?;
+            self.writer.write_all(&buf[..n])?;
+            self.writer.get_mut().add(n as u64);
+            sent += n as u64;
+        }
+        self.writer.write_all(&[CHUNK_END])?;
+        Ok(sent)
+    }
+
+    /// 对端不支持分块时直接发送文件体，同样受限速控制，返回实际发送的字节数
+    fn send_raw(&mut self, file: &mut impl Read) -> std::io::Result<u64> {
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut sent = 0;
+        loop {
+            let len = self.control.rate_limit().slice_len(CHUNK_SIZE);
+            let n = file.read(&mut buf[..len])?;
+            if n == 0 {
+                return Ok(sent);
+            }
+            self.control.rate_limit().consume(n);
+            self.writer.write_all(&buf[..n])?;
+            sent += n as u64;
+        }
+    }
+
+    /// 暂停期间阻塞写入，对端支持时按间隔发送保活标记，取消也会结束等待
+    fn wait_while_paused(&mut self, keepalive: u8) -> anyhow::Result<()> {
+        if !self.control.is_paused() {
+            return Ok(());
+        }
+
+        self.writer.flush()?;
+        let paused_at = Instant::now();
+        let mut last_keepalive = paused_at;
+        while self.control.is_paused() && !self.control.is_cancelled() {
+            std::thread::sleep(CANCEL_POLL_INTERVAL);
+
+            if self.capabilities.contains(Capabilities::KEEPALIVE)
+                && last_keepalive.elapsed() >= KEEPALIVE_INTERVAL
+            {
+                self.writer.write_all(&[keepalive])?;
+                self.writer.flush()?;
+                last_keepalive = Instant::now();
+            }
+        }
+        self.writer.get_mut().exclude(paused_at.elapsed());
+        Ok(())
+    }
+
+    /// 在条目之间通知对端本次传输已取消，对端不支持时直接断开
+    fn abort(&mut self) -> anyhow::Result<()> {
+        if self.capabilities.contains(Capabilities::CHUNKED) {
+            self.writer.write_all(&[TYPE_ABORT])?;
+            self.writer.flush()?;
+        }
+        anyhow::bail!(t!("cancelled"));
+    }
+
+    /// 读取接收端已有的字节数和前缀摘要，与本地文件一致时从该位置继续发送，否则从头发送，
+    /// 接收端要求跳过时返回 `None`
+    fn negotiate_resume(
+        &mut self,
+        file: &mut HashReader<std::fs::File>,
+        size: u64,
+    ) -> anyhow::Result<Option<u64>> {
+        self.writer.flush()?;
+        let stream = self.writer.get_mut().get_mut();
+
+        // 接收端询问如何处理已有文件时可能等待很久，期间仍要响应取消；
+        // 对端此时在等用户回答，读不到中止标记，直接断开连接
+        while !stream.poll_readable(CANCEL_POLL_INTERVAL)? {
+            if self.control.is_cancelled() {
+                anyhow::bail!(t!("cancelled"));
+            }
+        }
+
+        let mut tag = [0];
+        stream.read_exact(&mut tag)?;
+
+        let offset = match tag[0] {
+            RESUME_NONE => 0,
+            RESUME_SKIP if self.capabilities.contains(Capabilities::SKIP) => return Ok(None),
+            RESUME_FROM => {
+                let mut offset = [0; 8];
+                stream.read_exact(&mut offset)?;
+                let offset = u64::from_be_bytes(offset);
+
+                let mut expected = [0; HASH_LEN];
+                stream.read_exact(&mut expected)?;
+
+                let matched = offset <= size
+                    && std::io::copy(&mut (&mut *file).take(offset), &mut std::io::sink())?
+                        == offset
+                    && file.finalize() == expected;
+                if matched {
+                    offset
+                } else {
+                    file.rewind()?;
+                    0
+                }
+            }
+            tag => anyhow::bail!("{} : {tag}", t!("incompatible_peer")),
+        };
+
+        self.writer.write_all(&offset.to_be_bytes())?;
+        Ok(Some(offset))
+    }
 }
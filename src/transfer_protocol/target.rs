@@ -0,0 +1,222 @@
+use rust_i18n::t;
+use std::{
+    fmt::{self, Display},
+    io,
+    net::{IpAddr, Ipv6Addr, SocketAddr, ToSocketAddrs},
+    str::FromStr,
+    vec,
+};
+
+/// 用户输入的发送目标：主机名、IPv4 或 IPv6（可带 `%` 区域），端口可以一起写在后面
+///
+/// 支持 `buildbox.local`、`192.168.1.20:8000`、`fe80::1%eth0` 和 `[fe80::1%eth0]:8000`
+#[derive(Clone, PartialEq, Eq)]
+pub struct Target {
+    /// 不带方括号的主机名或 IP
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl Target {
+    /// 输入里没有端口时使用 `port`
+    pub fn with_default_port(&self, port: u16) -> Self {
+        Self {
+            host: self.host.clone(),
+            port: self.port.or(Some(port)),
+        }
+    }
+
+    /// 主机是 IP 地址时不需要解析
+    pub fn is_ip(&self) -> bool {
+        self.host.parse::<IpAddr>().is_ok() || self.host.contains('%')
+    }
+
+    /// 解析出全部地址，按系统返回的顺序去重，会阻塞
+    pub fn resolve(&self) -> io::Result<Vec<SocketAddr>> {
+        let port = self.port.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                t!("port_validation").to_string(),
+            )
+        })?;
+
+        // 带区域的 IPv6 交给系统的 getaddrinfo 解析，网卡名和序号都支持
+        let mut addrs = Vec::new();
+        for addr in (self.host.as_str(), port).to_socket_addrs()? {
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+        Ok(addrs)
+    }
+}
+
+impl From<IpAddr> for Target {
+    fn from(ip: IpAddr) -> Self {
+        Self {
+            host: ip.to_string(),
+            port: None,
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (host, port) = if let Some(rest) = s.strip_prefix('[') {
+            // 方括号只用来包住 IPv6
+            let (host, rest) = rest
+                .split_once(']')
+                .filter(|(host, _)| host.contains(':'))
+                .ok_or_else(|| anyhow::anyhow!("{} : {s}", t!("invalid_address")))?;
+            let port = match rest {
+                "" => None,
+                _ => Some(
+                    rest.strip_prefix(':')
+                        .ok_or_else(|| anyhow::anyhow!("{} : {s}", t!("invalid_address")))?,
+                ),
+            };
+            (host, port)
+        } else {
+            // 多个冒号时只能是没有方括号、也就没有端口的 IPv6
+            match s.split_once(':') {
+                Some((host, port)) if !port.contains(':') => (host, Some(port)),
+                _ => (s, None),
+            }
+        };
+
+        let port = port
+            .map(|p| p.parse::<u16>())
+            .transpose()
+            .map_err(|_| anyhow::anyhow!(t!("port_validation")))?;
+        if !is_valid_host(host) {
+            anyhow::bail!("{} : {s}", t!("invalid_address"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.host.contains(':'), self.port) {
+            (true, Some(port)) => write!(f, "[{}]:{port}", self.host),
+            (false, Some(port)) => write!(f, "{}:{port}", self.host),
+            (_, None) => write!(f, "{}", self.host),
+        }
+    }
+}
+
+/// 错误信息和传输记录里按输入的形式显示
+impl fmt::Debug for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// 不带端口时无法连接，返回 `InvalidInput`
+impl ToSocketAddrs for Target {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        Ok(self.resolve()?.into_iter())
+    }
+}
+
+/// IP 地址（IPv6 可带区域）或由字母、数字、`-`、`_` 组成的点分主机名
+fn is_valid_host(host: &str) -> bool {
+    if host.contains(':') {
+        return match host.split_once('%') {
+            Some((ip, zone)) => ip.parse::<Ipv6Addr>().is_ok() && !zone.is_empty(),
+            None => host.parse::<Ipv6Addr>().is_ok(),
+        };
+    }
+    if host.contains('%') {
+        return false;
+    }
+    let name = host.strip_suffix('.').unwrap_or(host);
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && label
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> (String, Option<u16>) {
+        let target = s.parse::<Target>().unwrap();
+        (target.host, target.port)
+    }
+
+    #[test]
+    fn parses_host_names_and_ipv4() {
+        assert_eq!(parse("buildbox.local"), ("buildbox.local".into(), None));
+        assert_eq!(parse(" buildbox "), ("buildbox".into(), None));
+        assert_eq!(parse("buildbox:9000"), ("buildbox".into(), Some(9000)));
+        assert_eq!(
+            parse("192.168.1.20:8000"),
+            ("192.168.1.20".into(), Some(8000))
+        );
+    }
+
+    #[test]
+    fn parses_bare_ipv6_without_port() {
+        assert_eq!(parse("::1"), ("::1".into(), None));
+        assert_eq!(parse("fe80::1%eth0"), ("fe80::1%eth0".into(), None));
+        assert_eq!(parse("[::1]"), ("::1".into(), None));
+    }
+
+    #[test]
+    fn parses_bracketed_ipv6_with_port() {
+        assert_eq!(parse("[::1]:8000"), ("::1".into(), Some(8000)));
+        assert_eq!(
+            parse("[fe80::1%eth0]:8000"),
+            ("fe80::1%eth0".into(), Some(8000))
+        );
+    }
+
+    #[test]
+    fn rejects_empty_or_invalid_ports() {
+        for s in ["buildbox:", "[::1]:", "buildbox:70000", "buildbox:port"] {
+            assert!(s.parse::<Target>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_hosts() {
+        for s in [
+            "",
+            ":8000",
+            "bad host",
+            "a..b",
+            "[::1",
+            "[::1]8000",
+            "[buildbox]:8000",
+            "fe80::1%",
+            "1.2.3.4%eth0",
+        ] {
+            assert!(s.parse::<Target>().is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn displays_in_input_form() {
+        for s in ["buildbox:9000", "[fe80::1%eth0]:8000", "::1"] {
+            assert_eq!(s.parse::<Target>().unwrap().to_string(), s);
+        }
+        let target = "::1".parse::<Target>().unwrap().with_default_port(8000);
+        assert_eq!(target.to_string(), "[::1]:8000");
+        assert!(target.is_ip());
+    }
+}
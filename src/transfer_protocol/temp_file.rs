@@ -0,0 +1,134 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use walkdir::WalkDir;
+
+const TEMP_PREFIX: &str = ".";
+const TEMP_SUFFIX: &str = ".transfer-part";
+/// 常见文件系统单个文件名的字节上限
+const NAME_MAX: usize = 255;
+/// 名字过长时截短后附加的摘要的十六进制位数
+const NAME_HASH_LEN: usize = 16;
+/// 超过这么久没有写入的临时文件不再等对端续传
+const STALE_AFTER: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// 清理时最多进入的目录层数和检查的条目数，保存目录设成主目录时也不会扫描太久
+const MAX_DEPTH: usize = 8;
+const MAX_ENTRIES: usize = 100_000;
+
+/// 接收中的文件先写到同一目录下的 ".name.transfer-part"，改名为最终文件名前不会被当成完整文件
+///
+/// 连接中断时临时文件保留下来供续传，校验失败或对端取消时删除。
+/// 加上前后缀会超过文件名上限时截短原名并附加原名的摘要，同一个文件每次得到的临时名相同
+pub fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default();
+    let mut name = OsString::from(TEMP_PREFIX);
+    if file_name.len() + TEMP_PREFIX.len() + TEMP_SUFFIX.len() <= NAME_MAX {
+        name.push(file_name);
+    } else {
+        let hash = blake3::hash(file_name.as_encoded_bytes()).to_hex();
+        let keep = NAME_MAX - TEMP_PREFIX.len() - TEMP_SUFFIX.len() - NAME_HASH_LEN - 1;
+        let lossy = file_name.to_string_lossy();
+        let mut end = keep.min(lossy.len());
+        while !lossy.is_char_boundary(end) {
+            end -= 1;
+        }
+        name.push(&lossy[..end]);
+        name.push("~");
+        name.push(&hash[..NAME_HASH_LEN]);
+    }
+    name.push(TEMP_SUFFIX);
+    path.with_file_name(name)
+}
+
+fn is_temp(path: &Path) -> bool {
+    // 按字节比较，不是 UTF-8 的文件名也能识别
+    path.file_name().is_some_and(|name| {
+        let name = name.as_encoded_bytes();
+        name.len() > TEMP_PREFIX.len() + TEMP_SUFFIX.len()
+            && name.starts_with(TEMP_PREFIX.as_bytes())
+            && name.ends_with(TEMP_SUFFIX.as_bytes())
+    })
+}
+
+fn is_stale(entry: &walkdir::DirEntry, now: SystemTime) -> bool {
+    entry
+        .metadata()
+        .ok()
+        .and_then(|m| m.modified().ok())
+        .is_some_and(|modified| now.duration_since(modified).unwrap_or_default() > STALE_AFTER)
+}
+
+/// 把临时文件写入磁盘后改名为最终文件名，已有同名文件时直接替换
+pub fn persist(file: &File, temp: &Path, target: &Path) -> std::io::Result<()> {
+    file.sync_all()?;
+    std::fs::rename(temp, target)?;
+
+    // 改名本身记录在目录里，Unix 上还要同步目录才能在断电后保留
+    #[cfg(unix)]
+    if let Some(parent) = target.parent() {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// 删除 `dir` 下很久没有写入的临时文件，返回删除的数量
+///
+/// 最近中断的临时文件保留下来，对端重新发送时还能从已有的前缀续传
+pub fn remove_stale(dir: &Path) -> usize {
+    let now = SystemTime::now();
+    WalkDir::new(dir)
+        .max_depth(MAX_DEPTH)
+        .into_iter()
+        .take(MAX_ENTRIES)
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && is_temp(entry.path()))
+        .filter(|entry| is_stale(entry, now))
+        .filter(|entry| std::fs::remove_file(entry.path()).is_ok())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_names_keep_the_original_name() {
+        let temp = temp_path(Path::new("dir/report.pdf"));
+        assert_eq!(temp, Path::new("dir/.report.pdf.transfer-part"));
+        assert!(is_temp(&temp));
+    }
+
+    #[test]
+    fn long_names_stay_within_name_max() {
+        for len in [NAME_MAX - 20, NAME_MAX - 10, NAME_MAX] {
+            let name = "a".repeat(len);
+            let temp = temp_path(&Path::new("dir").join(&name));
+            let temp_name = temp.file_name().unwrap();
+            assert!(temp_name.len() <= NAME_MAX, "{len}");
+            assert!(is_temp(&temp));
+            assert_eq!(temp, temp_path(&Path::new("dir").join(&name)));
+        }
+
+        // 截短后前缀相同的两个名字仍然得到不同的临时名
+        let a = temp_path(Path::new(&format!("{}-a", "x".repeat(NAME_MAX - 2))));
+        let b = temp_path(Path::new(&format!("{}-b", "x".repeat(NAME_MAX - 2))));
+        assert_ne!(a, b);
+
+        // 不会从多字节字符中间截断
+        let temp = temp_path(Path::new(&"文".repeat(NAME_MAX / 3)));
+        assert!(temp.to_str().is_some());
+        assert!(temp.as_os_str().len() <= NAME_MAX);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn matches_non_utf8_temp_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+        assert!(is_temp(&temp_path(path)));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{
+    address_book::SavedPeer,
+    app_state::{Language, SenderState},
+    sender::Target,
+};
+use dioxus::prelude::*;
+use rust_i18n::t;
+
+#[component]
+pub fn AddressBookPage() -> Element {
+    let mut address_book = use_context::<SenderState>().address_book;
+
+    let mut name = use_signal(String::new);
+    let mut host = use_signal(String::new);
+    let mut port = use_signal(|| "8000".to_string());
+    let mut error = use_signal(|| None::<String>);
+    // 正在编辑的条目原来的名字，改名保存时要删掉旧条目
+    let mut editing = use_signal(|| None::<String>);
+
+    let language = use_context::<Signal<Language>>();
+    _ = language.read();
+
+    let mut clear_form = move || {
+        name.set(String::new());
+        host.set(String::new());
+        port.set("8000".to_string());
+        error.set(None);
+        editing.set(None);
+    };
+
+    let save = move |_| {
+        let name = name().trim().to_string();
+        if name.is_empty() || host().trim().is_empty() {
+            error.set(Some(t!("peer_required").to_string()));
+            return;
+        }
+        // 和发送页的地址栏一样解析，地址里带端口时以它为准
+        let target = match host().parse::<Target>() {
+            Ok(target) => target,
+            Err(e) => {
+                error.set(Some(e.to_string()));
+                return;
+            }
+        };
+        let port = match target.port {
+            Some(port) => port,
+            None => match port().trim().parse() {
+                Ok(port) => port,
+                Err(_) => {
+                    error.set(Some(t!("port_validation").to_string()));
+                    return;
+                }
+            },
+        };
+        let peer = SavedPeer {
+            name,
+            host: target.host,
+            port,
+        };
+
+        let mut book = address_book.write();
+        if let Some(old) = editing() {
+            book.peers.retain(|p| p.name != old);
+        }
+        book.save_peer(peer);
+        drop(book);
+        clear_form();
+    };
+
+    rsx! {
+        div { class: "flex gap-4 h-full",
+            div { class: "w-80 flex flex-col gap-2 shadow rounded-box bg-base-100 p-4",
+                h2 { class: "text-xl font-bold mb-2",
+                    if editing.read().is_some() {
+                        r#"{t!("edit_peer")}"#
+                    } else {
+                        r#"{t!("add_peer")}"#
+                    }
+                }
+                fieldset { class: "fieldset",
+                    legend { class: "fieldset-legend text-gray-500", r#"{t!("peer_name")}"# }
+                    input {
+                        class: "input",
+                        r#type: "text",
+                        value: "{name}",
+                        oninput: move |e| name.set(e.value()),
+                    }
+                }
+                fieldset { class: "fieldset",
+                    legend { class: "fieldset-legend text-gray-500", r#"{t!("host")}"# }
+                    input {
+                        class: "input",
+                        r#type: "text",
+                        placeholder: "192.168.1.1",
+                        value: "{host}",
+                        oninput: move |e| host.set(e.value()),
+                    }
+                }
+                fieldset { class: "fieldset",
+                    legend { class: "fieldset-legend text-gray-500", r#"{t!("port")}"# }
+                    input {
+                        class: "input",
+                        r#type: "number",
+                        placeholder: "8000",
+                        value: "{port}",
+                        oninput: move |e| port.set(e.value()),
+                    }
+                }
+                p { class: "text-error", {error} }
+                div { class: "flex gap-2 mt-2",
+                    button { class: "btn btn-info flex-1", onclick: save, r#"{t!("save")}"# }
+                    if editing.read().is_some() {
+                        button {
+                            class: "btn btn-ghost",
+                            onclick: move |_| clear_form(),
+                            r#"{t!("cancel")}"#
+                        }
+                    }
+                }
+            }
+            div { class: "flex-1 flex flex-col gap-4 min-h-0",
+                div { class: "flex-1 min-h-0 overflow-auto shadow rounded-box bg-base-100 p-4",
+                    h2 { class: "text-xl font-bold mb-2", r#"{t!("saved_peers")}"# }
+                    if address_book.read().peers.is_empty() {
+                        p { class: "text-gray-500", r#"{t!("no_saved_peers")}"# }
+                    } else {
+                        table { class: "table",
+                            thead {
+                                tr {
+                                    th { r#"{t!("peer_name")}"# }
+                                    th { r#"{t!("host")}"# }
+                                    th { r#"{t!("port")}"# }
+                                    th {}
+                                }
+                            }
+                            tbody {
+                                for peer in address_book.read().peers.iter().cloned() {
+                                    tr { key: "{peer.name}",
+                                        td { "{peer.name}" }
+                                        td { class: "font-mono", "{peer.host}" }
+                                        td { class: "font-mono", "{peer.port}" }
+                                        td { class: "flex gap-2 justify-end",
+                                            button {
+                                                class: "btn btn-xs btn-ghost",
+                                                onclick: {
+                                                    let peer = peer.clone();
+                                                    move |_| {
+                                                        name.set(peer.name.clone());
+                                                        host.set(peer.host.clone());
+                                                        port.set(peer.port.to_string());
+                                                        error.set(None);
+                                                        editing.set(Some(peer.name.clone()));
+                                                    }
+                                                },
+                                                r#"{t!("edit")}"#
+                                            }
+                                            button {
+                                                class: "btn btn-xs btn-error btn-outline",
+                                                onclick: move |_| address_book.write().peers.retain(|p| p.name != peer.name),
+                                                r#"{t!("delete")}"#
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                div { class: "h-1/3 overflow-auto shadow rounded-box bg-base-100 p-4",
+                    div { class: "flex items-center justify-between mb-2",
+                        h2 { class: "text-xl font-bold", r#"{t!("recent_destinations")}"# }
+                        if !address_book.read().recent.is_empty() {
+                            button {
+                                class: "btn btn-xs btn-ghost",
+                                onclick: move |_| address_book.write().recent.clear(),
+                                r#"{t!("clear")}"#
+                            }
+                        }
+                    }
+                    if address_book.read().recent.is_empty() {
+                        p { class: "text-gray-500", r#"{t!("no_recent_destinations")}"# }
+                    }
+                    for destination in address_book.read().recent.iter().cloned() {
+                        div {
+                            key: "{destination.host}:{destination.port}",
+                            class: "flex items-center justify-between py-1",
+                            span { class: "font-mono", "{destination.host}:{destination.port}" }
+                            // 填进左边的表单，起个名字就能保存
+                            button {
+                                class: "btn btn-xs btn-ghost",
+                                onclick: move |_| {
+                                    clear_form();
+                                    host.set(destination.host.clone());
+                                    port.set(destination.port.to_string());
+                                },
+                                r#"{t!("add_peer")}"#
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
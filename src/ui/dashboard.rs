@@ -1,5 +1,8 @@
 use crate::{
-    app_state::{use_receiver_state, use_sender_state, Language},
+    app_state::{use_receiver_state, use_sender_state, Language, ReceiverState, SenderState},
+    receiver::Prompt,
+    settings,
+    transfer_protocol::progress::Progress,
     ui::Route,
 };
 use dioxus::prelude::*;
@@ -11,6 +14,9 @@ use tokio::time::interval;
 #[component]
 pub fn DashboardLayout() -> Element {
     let current_route = use_route::<Route>();
+    // 启动时读取一次，之后记录最近保存的内容，用来判断设置是否有变化
+    let mut saved_settings = use_signal(settings::load);
+    let initial = saved_settings.peek().clone();
 
     let receiver_logs = use_signal(Vec::new);
     let receiver_tx = use_coroutine(move |rx: UnboundedReceiver<String>| async move {
@@ -21,27 +27,78 @@ pub fn DashboardLayout() -> Element {
     let sender_log_tx = use_coroutine(move |rx: UnboundedReceiver<String>| async move {
         handle_logs(rx, sender_logs).await
     });
-    let mut send_progress = use_signal(|| (0.0, "0.00MB/s".to_string()));
-    let sender_progress_tx =
-        use_coroutine(move |mut rx: UnboundedReceiver<(f64, String)>| async move {
-            while let Some((percent, speed)) = rx.next().await {
-                send_progress.set((percent, speed));
-            }
-        });
+    let mut send_progress = use_signal(Progress::default);
+    let sender_progress_tx = use_coroutine(move |mut rx: UnboundedReceiver<Progress>| async move {
+        while let Some(progress) = rx.next().await {
+            send_progress.set(progress);
+        }
+    });
     let sender_state = use_sender_state(
         Signal::new(sender_log_tx.tx()),
         sender_logs,
         Signal::new(sender_progress_tx.tx()),
         send_progress,
+        &initial,
+    );
+    let (sender_port, send_limit) = (
+        sender_state.port_field.value,
+        sender_state.rate_limit_field.value,
     );
     use_context_provider(|| sender_state);
 
-    let receiver_state = use_receiver_state(Signal::new(receiver_tx.tx()), receiver_logs);
+    let mut receiver_prompts = use_signal(Vec::new);
+    let receiver_prompt_tx = use_coroutine(move |mut rx: UnboundedReceiver<Prompt>| async move {
+        while let Some(prompt) = rx.next().await {
+            receiver_prompts.push(prompt);
+        }
+    });
+    let mut receiver_pairing_code = use_signal(String::new);
+    let receiver_code_tx = use_coroutine(move |mut rx: UnboundedReceiver<String>| async move {
+        while let Some(code) = rx.next().await {
+            receiver_pairing_code.set(code);
+        }
+    });
+    let receiver_state = use_receiver_state(
+        Signal::new(receiver_tx.tx()),
+        receiver_logs,
+        Signal::new(receiver_prompt_tx.tx()),
+        receiver_prompts,
+        Signal::new(receiver_code_tx.tx()),
+        receiver_pairing_code,
+        &initial,
+    );
+    let (receiver_port, save_dir, receive_limit, conflict_policy) = (
+        receiver_state.port_field.value,
+        receiver_state.dir,
+        receiver_state.rate_limit_field.value,
+        receiver_state.conflict_policy,
+    );
     use_context_provider(|| receiver_state);
 
-    use_hook(|| rust_i18n::set_locale("en"));
-    let language = use_signal(|| Language::English);
+    use_hook(|| rust_i18n::set_locale(initial.language.locale()));
+    let language = use_signal(|| initial.language.clone());
     use_context_provider(|| language);
+    let theme = use_signal(|| initial.theme.clone());
+
+    let settings_log_tx = sender_log_tx.tx();
+    use_effect(move || {
+        let current = settings::Settings {
+            sender_port: sender_port(),
+            receiver_port: receiver_port(),
+            save_dir: save_dir(),
+            theme: theme(),
+            language: language(),
+            send_limit: send_limit(),
+            receive_limit: receive_limit(),
+            conflict_policy: conflict_policy(),
+        };
+        if current != *saved_settings.peek() {
+            if let Err(e) = settings::save(&current) {
+                _ = settings_log_tx.unbounded_send(format!("{} : {e}", t!("settings_fail")));
+            }
+            saved_settings.set(current);
+        }
+    });
 
     _ = language.read();
 
@@ -88,8 +145,48 @@ pub fn DashboardLayout() -> Element {
                         }
                         span { class: "text-xs", r#"{t!("receive")}"# }
                     }
+                    Link {
+                        class: if current_route == Route::AddressBookPage { "btn btn-ghost btn-square btn-xl flex flex-col bg-blue-500/10 text-blue-500" } else { "btn btn-ghost btn-square btn-xl flex flex-col text-gray-500" },
+                        to: Route::AddressBookPage,
+                        svg {
+                            class: "lucide lucide-book-user-icon lucide-book-user",
+                            fill: "none",
+                            height: "24",
+                            stroke: "currentColor",
+                            stroke_linecap: "round",
+                            stroke_linejoin: "round",
+                            stroke_width: "2",
+                            view_box: "0 0 24 24",
+                            width: "24",
+                            xmlns: "http://www.w3.org/2000/svg",
+                            path { d: "M15 13a3 3 0 1 0-6 0" }
+                            path { d: "M4 19.5v-15A2.5 2.5 0 0 1 6.5 2H19a1 1 0 0 1 1 1v18a1 1 0 0 1-1 1H6.5a1 1 0 0 1 0-5H20" }
+                            circle { cx: "12", cy: "8", r: "2" }
+                        }
+                        span { class: "text-xs", r#"{t!("address_book")}"# }
+                    }
+                    Link {
+                        class: if current_route == Route::HistoryPage { "btn btn-ghost btn-square btn-xl flex flex-col bg-blue-500/10 text-blue-500" } else { "btn btn-ghost btn-square btn-xl flex flex-col text-gray-500" },
+                        to: Route::HistoryPage,
+                        svg {
+                            class: "lucide lucide-history-icon lucide-history",
+                            fill: "none",
+                            height: "24",
+                            stroke: "currentColor",
+                            stroke_linecap: "round",
+                            stroke_linejoin: "round",
+                            stroke_width: "2",
+                            view_box: "0 0 24 24",
+                            width: "24",
+                            xmlns: "http://www.w3.org/2000/svg",
+                            path { d: "M3 12a9 9 0 1 0 9-9 9.75 9.75 0 0 0-6.74 2.74L3 8" }
+                            path { d: "M3 3v5h5" }
+                            path { d: "M12 7v5l4 2" }
+                        }
+                        span { class: "text-xs", r#"{t!("history")}"# }
+                    }
                 }
-                Settings {}
+                Settings { theme }
             }
             div { class: "flex-1 py-4 pr-8", Outlet::<Route> {} }
         }
@@ -121,14 +218,37 @@ async fn handle_logs(mut rx: UnboundedReceiver<String>, mut logs: Signal<Vec<Str
     }
 }
 #[component]
-fn Settings() -> Element {
+fn Settings(mut theme: Signal<String>) -> Element {
     let mut is_open = use_signal(|| false);
-    let mut theme = use_signal(|| "light".to_string());
     let mut language = use_context::<Signal<Language>>();
+    let SenderState {
+        port_field: mut send_port,
+        rate_limit_field: mut send_limit,
+        ..
+    } = use_context::<SenderState>();
+    let ReceiverState {
+        port_field: mut receive_port,
+        rate_limit_field: mut receive_limit,
+        dir: mut save_dir,
+        mut conflict_policy,
+        ..
+    } = use_context::<ReceiverState>();
 
     let change_theme = move |e: Event<FormData>| {
         theme.set(e.value());
     };
+    let reset = move |_| {
+        let defaults = settings::Settings::default();
+        send_port.set(defaults.sender_port);
+        receive_port.set(defaults.receiver_port);
+        save_dir.set(defaults.save_dir);
+        send_limit.set(defaults.send_limit);
+        receive_limit.set(defaults.receive_limit);
+        conflict_policy.set(defaults.conflict_policy);
+        theme.set(defaults.theme);
+        rust_i18n::set_locale(defaults.language.locale());
+        language.set(defaults.language);
+    };
 
     rsx! {
         button {
@@ -274,10 +394,37 @@ fn Settings() -> Element {
                         }
                     }
                 }
-                button {
-                    class: "btn btn-info self-end mt-4",
-                    onclick: move |_| is_open.set(false),
-                    r#"{t!("close")}"#
+                div { class: "flex items-center justify-between w-1/2",
+                    label { class: "text-2xl", r#"{t!("send_limit")} :"# }
+                    input {
+                        class: "input w-24",
+                        r#type: "number",
+                        min: "0",
+                        step: "0.5",
+                        placeholder: r#"{t!("unlimited")}"#,
+                        value: "{send_limit.raw_value}",
+                        oninput: send_limit.oninput,
+                    }
+                }
+                div { class: "flex items-center justify-between w-1/2",
+                    label { class: "text-2xl", r#"{t!("receive_limit")} :"# }
+                    input {
+                        class: "input w-24",
+                        r#type: "number",
+                        min: "0",
+                        step: "0.5",
+                        placeholder: r#"{t!("unlimited")}"#,
+                        value: "{receive_limit.raw_value}",
+                        oninput: receive_limit.oninput,
+                    }
+                }
+                div { class: "flex self-end gap-2 mt-4",
+                    button { class: "btn btn-ghost", onclick: reset, r#"{t!("reset_defaults")}"# }
+                    button {
+                        class: "btn btn-info",
+                        onclick: move |_| is_open.set(false),
+                        r#"{t!("close")}"#
+                    }
                 }
             }
         }
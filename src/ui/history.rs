@@ -0,0 +1,227 @@
+use crate::{
+    app_state::{Language, SenderState},
+    history::{self, Direction, Outcome, TransferRecord},
+    sender::Target,
+    transfer_protocol::{format_size, progress::format_duration},
+    ui::Route,
+};
+use dioxus::prelude::*;
+use rust_i18n::t;
+
+#[component]
+pub fn HistoryPage() -> Element {
+    let mut records = use_signal(|| history::load().unwrap_or_default());
+    let mut direction = use_signal(|| None::<Direction>);
+    let mut outcome = use_signal(|| None::<Outcome>);
+    let mut search = use_signal(String::new);
+
+    let language = use_context::<Signal<Language>>();
+    _ = language.read();
+
+    let query = search.read().to_lowercase();
+    let shown: Vec<TransferRecord> = records
+        .read()
+        .iter()
+        .filter(|r| direction().is_none_or(|d| r.direction == d))
+        .filter(|r| outcome().is_none_or(|o| r.outcome == o))
+        .filter(|r| query.is_empty() || matches(r, &query))
+        .cloned()
+        .collect();
+
+    rsx! {
+        div { class: "flex flex-col h-full gap-4 shadow rounded-box bg-base-100 p-4",
+            div { class: "flex items-center gap-2",
+                input {
+                    class: "input flex-1",
+                    r#type: "search",
+                    placeholder: r#"{t!("search_history")}"#,
+                    value: "{search}",
+                    oninput: move |e| search.set(e.value()),
+                }
+                select {
+                    class: "select w-40",
+                    onchange: move |e| {
+                        direction
+                            .set(
+                                match e.value().as_str() {
+                                    "send" => Some(Direction::Send),
+                                    "receive" => Some(Direction::Receive),
+                                    _ => None,
+                                },
+                            )
+                    },
+                    option { value: "", r#"{t!("all_directions")}"# }
+                    option { value: "send", r#"{t!("send")}"# }
+                    option { value: "receive", r#"{t!("receive")}"# }
+                }
+                select {
+                    class: "select w-40",
+                    onchange: move |e| {
+                        outcome
+                            .set(
+                                Outcome::ALL.into_iter().find(|o| outcome_key(*o) == e.value()),
+                            )
+                    },
+                    option { value: "", r#"{t!("all_outcomes")}"# }
+                    for o in Outcome::ALL {
+                        option { value: outcome_key(o), {outcome_label(o)} }
+                    }
+                }
+                button {
+                    class: "btn btn-ghost",
+                    onclick: move |_| records.set(history::load().unwrap_or_default()),
+                    r#"{t!("reload")}"#
+                }
+            }
+            div { class: "flex-1 min-h-0 overflow-auto",
+                if shown.is_empty() {
+                    p { class: "text-gray-500 text-center mt-8", r#"{t!("no_history")}"# }
+                } else {
+                    table { class: "table table-pin-rows",
+                        thead {
+                            tr {
+                                th { r#"{t!("time")}"# }
+                                th { r#"{t!("direction")}"# }
+                                th { r#"{t!("peer")}"# }
+                                th { r#"{t!("content")}"# }
+                                th { r#"{t!("size")}"# }
+                                th { r#"{t!("time_taken")}"# }
+                                th { r#"{t!("speed")}"# }
+                                th { r#"{t!("outcome")}"# }
+                                th {}
+                            }
+                        }
+                        tbody {
+                            for record in shown {
+                                HistoryRow { record }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn HistoryRow(record: TransferRecord) -> Element {
+    let sender_state = use_context::<SenderState>();
+    let navigator = use_navigator();
+
+    let direction = match record.direction {
+        Direction::Send => t!("send"),
+        Direction::Receive => t!("receive"),
+    };
+    let content = if record.names.is_empty() {
+        record
+            .local_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        record.names.join(", ")
+    };
+    let badge = match record.outcome {
+        Outcome::Completed => "badge badge-success",
+        Outcome::Rejected | Outcome::Cancelled => "badge badge-warning",
+        Outcome::Failed => "badge badge-error",
+    };
+    let speed = record.average_speed();
+    let time = record.time.format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let resend = {
+        let record = record.clone();
+        move |_| {
+            let SenderState {
+                mut file,
+                mut ip_field,
+                mut port_field,
+                ..
+            } = sender_state;
+            file.set(record.local_path.clone());
+            if let Ok(Target {
+                host,
+                port: Some(port),
+            }) = record.peer.parse()
+            {
+                ip_field.set(Target { host, port: None });
+                port_field.set(port);
+            }
+            // 配对码每次都不同，由用户在发送页填写后再发送
+            navigator.push(Route::SenderPage);
+        }
+    };
+
+    rsx! {
+        tr {
+            td { class: "whitespace-nowrap", "{time}" }
+            td { "{direction}" }
+            td {
+                p { class: "font-mono", "{record.peer}" }
+                if !record.peer_name.is_empty() {
+                    p { class: "text-gray-500", "{record.peer_name}" }
+                }
+            }
+            td {
+                class: "max-w-xs truncate",
+                title: "{record.local_path.display()}",
+                "{content}"
+                if record.files > 0 {
+                    span { class: "text-gray-500", r#" ({record.files} {t!("files")})"# }
+                }
+            }
+            td { "{format_size(record.bytes as f64)}" }
+            td { "{format_duration(record.elapsed)}" }
+            td {
+                if speed > 0.0 {
+                    "{format_size(speed)}/s"
+                }
+            }
+            td {
+                span {
+                    class: badge,
+                    title: record.error.clone().unwrap_or_default(),
+                    {outcome_label(record.outcome)}
+                }
+            }
+            td {
+                if record.direction == Direction::Send {
+                    button { class: "btn btn-xs btn-info", onclick: resend, r#"{t!("resend")}"# }
+                }
+            }
+        }
+    }
+}
+
+/// 在地址、设备名、路径、文件名和错误里查找，`query` 已转为小写
+fn matches(record: &TransferRecord, query: &str) -> bool {
+    [
+        record.peer.as_str(),
+        record.peer_name.as_str(),
+        &record.local_path.to_string_lossy(),
+        &record.names.join("\n"),
+        record.error.as_deref().unwrap_or_default(),
+    ]
+    .iter()
+    .any(|field| field.to_lowercase().contains(query))
+}
+
+fn outcome_key(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Completed => "completed",
+        Outcome::Rejected => "rejected",
+        Outcome::Cancelled => "cancelled",
+        Outcome::Failed => "failed",
+    }
+}
+
+fn outcome_label(outcome: Outcome) -> String {
+    match outcome {
+        Outcome::Completed => t!("completed"),
+        Outcome::Rejected => t!("rejected"),
+        Outcome::Cancelled => t!("cancelled"),
+        Outcome::Failed => t!("failed"),
+    }
+    .to_string()
+}
@@ -1,9 +1,14 @@
+mod address_book;
 mod dashboard;
+mod history;
+mod progress;
 mod receiver;
 mod sender;
 
+use address_book::AddressBookPage;
 use dashboard::DashboardLayout;
 use dioxus::prelude::*;
+use history::HistoryPage;
 use receiver::ReceiverPage;
 use sender::SenderPage;
 
@@ -15,4 +20,8 @@ pub enum Route {
     SenderPage,
     #[route("/receiver")]
     ReceiverPage,
+    #[route("/address-book")]
+    AddressBookPage,
+    #[route("/history")]
+    HistoryPage,
 }
@@ -0,0 +1,57 @@
+use crate::transfer_protocol::{
+    format_size,
+    progress::{format_duration, Progress},
+};
+use dioxus::prelude::*;
+use rust_i18n::t;
+
+/// 进度环下方的字节数、文件数、当前文件、速度和剩余时间
+#[component]
+pub fn ProgressStats(progress: Progress) -> Element {
+    let done = format_size(progress.bytes_done as f64);
+    let total = format_size(progress.bytes_total as f64);
+    let current_speed = format_size(progress.current_speed);
+    let average_speed = format_size(progress.average_speed);
+    let eta = progress
+        .eta
+        .map(format_duration)
+        .unwrap_or_else(|| "--:--".to_string());
+
+    rsx! {
+        div { class: "flex flex-col items-center text-sm text-gray-500 max-w-full px-4",
+            p { "{done} / {total}" }
+            p { r#"{progress.files_done}/{progress.files_total} {t!("files")}"# }
+            if let Some(path) = progress.current_path.as_ref() {
+                p { class: "truncate max-w-full", title: "{path:?}",
+                    "{path.file_name().unwrap_or_default().to_string_lossy()}"
+                }
+            }
+            p { r#"{t!("speed")}: {current_speed}/s ({t!("average")} {average_speed}/s)"# }
+            p { r#"{t!("eta")}: {eta}"# }
+        }
+    }
+}
+
+/// 接收端每个正在进行的连接一张卡片
+#[component]
+pub fn ProgressCard(progress: Progress) -> Element {
+    let percent = progress.percent();
+    let peer = progress
+        .peer
+        .map(|peer| peer.to_string())
+        .unwrap_or_default();
+
+    rsx! {
+        div { class: "w-64 flex flex-col items-center gap-2 p-4 rounded-box bg-base-200",
+            p { class: "font-mono text-sm", "{peer}" }
+            div {
+                class: "radial-progress bg-blue-500 text-white border-blue-500 border-4",
+                role: "progressbar",
+                aria_valuenow: "{percent:.0}",
+                style: "--value:{percent:.0}; --size:4rem;",
+                "{percent:.0}%"
+            }
+            ProgressStats { progress }
+        }
+    }
+}
@@ -1,7 +1,12 @@
-use crate::app_state::{Language, ReceiverState};
-use crate::receiver::handle_receive;
+use crate::app_state::{receiver_events, Language, ReceiverState};
+use crate::receiver::{
+    advertise, handle_receive, ConflictAction, ConflictPolicy, Decision, Prompt, ReceiveOptions,
+};
+use crate::transfer_protocol::format_size;
+use crate::ui::progress::ProgressCard;
 use dioxus::{html::geometry::PixelsVector2D, prelude::*};
 use rust_i18n::t;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::atomic::Ordering::Relaxed;
 
@@ -12,7 +17,14 @@ pub fn ReceiverPage() -> Element {
     let mut dir = receiver_state.dir;
     let mut logs = receiver_state.logs;
     let log_tx = receiver_state.log_tx;
+    let prompt_tx = receiver_state.prompt_tx;
+    let code_tx = receiver_state.code_tx;
+    let progress_tx = receiver_state.progress_tx;
+    let transfers = receiver_state.transfers;
+    let pairing_code = receiver_state.pairing_code;
     let mut is_running = receiver_state.is_running;
+    let rate_limit = receiver_state.rate_limit;
+    let mut conflict_policy = receiver_state.conflict_policy;
 
     let mut log_container = use_signal(|| None::<Rc<MountedData>>);
 
@@ -38,6 +50,8 @@ pub fn ReceiverPage() -> Element {
     });
 
     rsx! {
+        ManifestDialog {}
+        ConflictDialog {}
         div { class: "flex flex-col h-full gap-8",
             div { class: "flex-1 min-h-0 flex flex-col items-center justify-center shadow rounded-lg bg-base-100",
                 fieldset { class: "fieldset",
@@ -67,6 +81,25 @@ pub fn ReceiverPage() -> Element {
                     p { class: "text-error", {port_field.error} }
                 
                 }
+                fieldset { class: "fieldset",
+                    legend { class: "fieldset-legend text-gray-500", r#"{t!("on_conflict")}"# }
+                    select {
+                        class: "select",
+                        disabled: is_running.read().load(Relaxed),
+                        onchange: move |e| {
+                            if let Ok(policy) = e.value().parse() {
+                                conflict_policy.set(policy);
+                            }
+                        },
+                        for policy in ConflictPolicy::ALL {
+                            option {
+                                value: policy.as_str(),
+                                selected: conflict_policy() == policy,
+                                {policy_label(policy)}
+                            }
+                        }
+                    }
+                }
                 button {
                     class: if is_running.read().load(Relaxed) { "btn btn-error text-white px-20 mt-6" } else { "btn btn-info px-20 mt-6" },
                     onclick: move |_| async move {
@@ -82,10 +115,30 @@ pub fn ReceiverPage() -> Element {
                             let is_running = is_running();
                             let dir = dir();
                             let log_tx = log_tx();
+                            let events = receiver_events(
+                                log_tx.clone(),
+                                prompt_tx(),
+                                code_tx(),
+                                progress_tx(),
+                            );
+                            let options = ReceiveOptions {
+                                rate_limit: rate_limit(),
+                                conflict_policy: conflict_policy(),
+                                pairing_code: None,
+                            };
                             let addr = format!("0.0.0.0:{}", port_field.value);
+                            let port = *port_field.value.read();
+                            let announce = is_running.clone();
+                            std::thread::spawn(move || advertise(port, announce));
 
                             std::thread::spawn(move || {
-                                match handle_receive(addr, dir, log_tx.clone(), is_running.clone()) {
+                                match handle_receive(
+                                    addr,
+                                    dir,
+                                    events,
+                                    is_running.clone(),
+                                    options,
+                                ) {
                                     Ok(()) => {
                                         is_running.store(false, Relaxed);
                                         _ = log_tx.unbounded_send(t!("stop_server").to_string());
@@ -107,6 +160,17 @@ pub fn ReceiverPage() -> Element {
                         r#"{t!("start_server")}"#
                     }
                 }
+                if is_running.read().load(Relaxed) {
+                    div { class: "flex flex-col items-center mt-6",
+                        p { class: "text-gray-500", r#"{t!("pairing_code")}"# }
+                        p { class: "text-3xl font-mono font-bold tracking-wide", "{pairing_code}" }
+                    }
+                }
+                div { class: "flex flex-wrap justify-center gap-4 mt-6 max-w-full overflow-y-auto",
+                    for progress in transfers.iter() {
+                        ProgressCard { key: "{progress.peer:?}", progress: progress.clone() }
+                    }
+                }
             }
             div { class: "h-1/3 fieldset shadow rounded-box bg-base-100 px-4 flex relative",
                 div { class: "absolute -top-3 left-4 flex items-center gap-2",
@@ -143,3 +207,139 @@ pub fn ReceiverPage() -> Element {
         }
     }
 }
+
+#[component]
+fn ManifestDialog() -> Element {
+    let mut prompts = use_context::<ReceiverState>().prompts;
+    let mut prompt_dir = use_signal(|| None::<PathBuf>);
+
+    let mut answer = move |accept: bool| {
+        if !matches!(prompts.read().first(), Some(Prompt::Manifest(_))) {
+            return;
+        }
+        let Prompt::Manifest(prompt) = prompts.write().remove(0) else {
+            return;
+        };
+        let save_path = prompt_dir.take().unwrap_or(prompt.save_path);
+        let decision = if accept {
+            Decision::Accept(save_path)
+        } else {
+            Decision::Reject
+        };
+        _ = prompt.reply.send(decision);
+    };
+
+    let Some((peer, manifest, save_path)) = prompts.read().first().and_then(|p| match p {
+        Prompt::Manifest(p) => Some((p.peer, p.manifest.clone(), p.save_path.clone())),
+        Prompt::Conflict(_) => None,
+    }) else {
+        return rsx! {};
+    };
+    let save_path = prompt_dir().unwrap_or(save_path);
+
+    rsx! {
+        dialog { class: "modal modal-open",
+            div { class: "modal-box flex flex-col gap-4",
+                h1 { class: "text-2xl font-bold", r#"{t!("incoming")}"# }
+                p { class: "text-gray-500", "{manifest.hostname} ({peer})" }
+                ul { class: "list bg-base-200 rounded-box max-h-40 overflow-y-auto",
+                    for name in manifest.top_level_names.iter() {
+                        li { class: "list-row break-all", "{name}" }
+                    }
+                }
+                p {
+                    r#"{manifest.file_count} {t!("files")}, {format_size(manifest.total_size as f64)}"#
+                }
+                fieldset { class: "fieldset",
+                    input {
+                        class: "file-input",
+                        r#type: "file",
+                        directory: true,
+                        onchange: move |e| {
+                            for file in e.files() {
+                                prompt_dir.set(Some(file.path()));
+                            }
+                        },
+                    }
+                    p { class: "label break-all whitespace-normal",
+                        r#"{t!("save_path")} : {save_path:?}"#
+                    }
+                }
+                div { class: "modal-action",
+                    button {
+                        class: "btn btn-error btn-outline",
+                        onclick: move |_| answer(false),
+                        r#"{t!("reject")}"#
+                    }
+                    button {
+                        class: "btn btn-info",
+                        onclick: move |_| answer(true),
+                        r#"{t!("accept")}"#
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn ConflictDialog() -> Element {
+    let mut prompts = use_context::<ReceiverState>().prompts;
+
+    let mut answer = move |action: ConflictAction| {
+        if !matches!(prompts.read().first(), Some(Prompt::Conflict(_))) {
+            return;
+        }
+        if let Prompt::Conflict(prompt) = prompts.write().remove(0) {
+            _ = prompt.reply.send(action);
+        }
+    };
+
+    let Some((peer, path, size, existing_size)) = prompts.read().first().and_then(|p| match p {
+        Prompt::Conflict(p) => Some((p.peer, p.path.clone(), p.size, p.existing_size)),
+        Prompt::Manifest(_) => None,
+    }) else {
+        return rsx! {};
+    };
+
+    rsx! {
+        dialog { class: "modal modal-open",
+            div { class: "modal-box flex flex-col gap-4",
+                h1 { class: "text-2xl font-bold", r#"{t!("file_exists")}"# }
+                p { class: "text-gray-500", "{peer}" }
+                p { class: "break-all", "{path:?}" }
+                p {
+                    r#"{t!("existing_file")} : {format_size(existing_size as f64)}, {t!("incoming_file")} : {format_size(size as f64)}"#
+                }
+                div { class: "modal-action",
+                    button {
+                        class: "btn btn-outline",
+                        onclick: move |_| answer(ConflictAction::Skip),
+                        r#"{t!("conflict_skip")}"#
+                    }
+                    button {
+                        class: "btn btn-info btn-outline",
+                        onclick: move |_| answer(ConflictAction::Rename),
+                        r#"{t!("conflict_rename")}"#
+                    }
+                    button {
+                        class: "btn btn-warning",
+                        onclick: move |_| answer(ConflictAction::Overwrite),
+                        r#"{t!("conflict_overwrite")}"#
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn policy_label(policy: ConflictPolicy) -> String {
+    match policy {
+        ConflictPolicy::Overwrite => t!("conflict_overwrite"),
+        ConflictPolicy::Skip => t!("conflict_skip"),
+        ConflictPolicy::SkipIfIdentical => t!("conflict_skip_identical"),
+        ConflictPolicy::Rename => t!("conflict_rename"),
+        ConflictPolicy::Ask => t!("conflict_ask"),
+    }
+    .to_string()
+}
@@ -1,13 +1,20 @@
 use crate::{
-    app_state::{Language, SenderState},
-    sender::handle_send,
+    app_state::{sender_events, Language, SenderState},
+    sender::{handle_send, LinkMode, Target, TransferControl},
+    transfer_protocol::progress::Progress,
+    ui::progress::ProgressStats,
 };
 use dioxus::{
     html::{geometry::PixelsVector2D, HasFileData},
     prelude::*,
 };
 use rust_i18n::t;
-use std::{rc::Rc, sync::atomic::Ordering::Relaxed};
+use std::{
+    path::PathBuf,
+    rc::Rc,
+    sync::{atomic::Ordering::Relaxed, Arc},
+    time::Duration,
+};
 
 #[component]
 pub fn SenderPage() -> Element {
@@ -15,20 +22,126 @@ pub fn SenderPage() -> Element {
 
     let sender_state = use_context::<SenderState>();
     let mut enable_directory = sender_state.enable_directory;
+    let mut link_mode = sender_state.link_mode;
     let mut ip_field = sender_state.ip_field;
     let mut port_field = sender_state.port_field;
     let mut file = sender_state.file;
+    let mut pairing_code = sender_state.pairing_code;
     let mut is_running = sender_state.is_running;
+    let mut last_send = sender_state.last_send;
+    let resumable = sender_state.resumable;
+    let mut control = sender_state.control;
+    let mut rate_limit_field = sender_state.rate_limit_field;
+    let rate_limit = sender_state.rate_limit;
     let log_tx = sender_state.log_tx;
     let mut logs = sender_state.logs;
     let progress_tx = sender_state.progress_tx;
     let mut progress = sender_state.progress;
+    let peers = sender_state.peers;
+    let address_book = sender_state.address_book;
+    let recent_tx = sender_state.recent_tx;
 
     let mut log_container = use_signal(|| None::<Rc<MountedData>>);
 
     let language = use_context::<Signal<Language>>();
     _ = language.read();
 
+    let mut start_send = move |target: Target, file: PathBuf| {
+        is_running.write().store(true, Relaxed);
+        resumable.read().store(false, Relaxed);
+        progress.set(Progress::default());
+        last_send.set(Some((target.clone(), file.clone())));
+        control.set(Arc::new(TransferControl::new(rate_limit())));
+        let control = control();
+        let running = is_running();
+        let resumable = resumable();
+        // 配对码只能用一次，发出后清空，续传时要填入接收端新显示的配对码
+        let pairing_code = pairing_code.replace(String::new());
+        let link_mode = link_mode();
+        let log_tx = log_tx();
+        let events = sender_events(log_tx.clone(), progress_tx());
+        let recent_tx = recent_tx();
+        std::thread::spawn(move || {
+            match handle_send(
+                target.clone(),
+                file,
+                &pairing_code,
+                events,
+                control.clone(),
+                link_mode,
+            ) {
+                Ok(()) => {
+                    running.store(false, Relaxed);
+                    _ = recent_tx.unbounded_send(target.to_string());
+                    _ = log_tx.unbounded_send(t!("send_over").to_string());
+                }
+                // 取消后接收端已删除不完整的文件，不再提供续传
+                Err(_) if control.is_cancelled() => {
+                    running.store(false, Relaxed);
+                    _ = log_tx.unbounded_send(t!("cancelled").to_string());
+                }
+                Err(e) => {
+                    resumable.store(true, Relaxed);
+                    running.store(false, Relaxed);
+                    _ = log_tx.unbounded_send(format!("{} : {}", t!("send_fail"), e));
+                }
+            }
+        });
+    };
+
+    // 从地址簿或最近目标里选中时填入地址和端口，配对码每次都要重新输入
+    let mut fill_destination = move |host: String, port: u16| {
+        match host.parse::<Target>() {
+            Ok(target) => ip_field.set(target),
+            Err(e) => {
+                ip_field.raw_value.set(host);
+                ip_field.error.set(Some(e.to_string()));
+            }
+        }
+        port_field.set(port);
+    };
+
+    // 输入主机名时在后台解析，显示发送时会依次尝试的地址，IP 地址不需要解析
+    let resolved = use_resource(move || async move {
+        let target = ip_field
+            .value
+            .read()
+            .with_default_port(*port_field.value.read());
+        if target.is_ip() {
+            return None;
+        }
+        // 等输入停下来再解析
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let result = tokio::task::spawn_blocking(move || target.resolve()).await;
+        Some(
+            result
+                .map_err(|e| e.to_string())
+                .and_then(|r| r.map_err(|e| e.to_string())),
+        )
+    });
+    let resolved_hint = match &*resolved.read() {
+        Some(None) => rsx! {},
+        Some(Some(Ok(addrs))) => {
+            let addrs = addrs
+                .iter()
+                .map(|a| a.ip().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            rsx! {
+                p { class: "text-gray-500 font-mono break-all", "→ {addrs}" }
+            }
+        }
+        Some(Some(Err(e))) => rsx! {
+            p { class: "text-warning break-all", r#"{t!("resolve_fail")} : {e}"# }
+        },
+        None => rsx! {
+            p { class: "text-gray-500 flex items-center gap-2",
+                span { class: "loading loading-spinner loading-xs" }
+                r#"{t!("resolving")}"#
+            }
+        },
+    };
+
     use_effect(move || {
         logs.read();
 
@@ -93,23 +206,129 @@ pub fn SenderPage() -> Element {
                                 onchange: move |evt| enable_directory.set(evt.checked()),
                             }
                         }
+                        div { class: "flex items-center gap-2 ",
+                            label {
+                                class: "text-lg text-gray-600",
+                                r#for: "preserve-links",
+                                r#"{t!("preserve_links")}"#
+                            }
+                            input {
+                                class: "checkbox checkbox-info checkbox-md",
+                                r#type: "checkbox",
+                                id: "preserve-links",
+                                checked: link_mode() == LinkMode::Preserve,
+                                onchange: move |evt| {
+                                    link_mode
+                                        .set(if evt.checked() { LinkMode::Preserve } else { LinkMode::Follow })
+                                },
+                            }
+                        }
                     }
                     p { class: "text-gray-500 break-all whitespace-normal",
                         r#"{t!("selected_file")} : {file:?}"#
                     }
                 }
                 div { class: "flex-1 flex flex-col p-4 items-center justify-center",
+                    fieldset { class: "fieldset w-full max-w-xs",
+                        legend { class: "fieldset-legend text-gray-500", r#"{t!("nearby_devices")}"# }
+                        div { class: "flex flex-col max-h-32 overflow-y-auto",
+                            for peer in peers.iter() {
+                                button {
+                                    key: "{peer.addr}",
+                                    class: "btn btn-sm btn-ghost justify-between",
+                                    disabled: !peer.is_compatible(),
+                                    title: "v{peer.version}",
+                                    onclick: {
+                                        let addr = peer.addr;
+                                        move |_| {
+                                            ip_field.set(addr.ip().into());
+                                            port_field.set(addr.port());
+                                        }
+                                    },
+                                    span { class: "truncate", "{peer.name}" }
+                                    span { class: "font-mono text-gray-500", "{peer.addr}" }
+                                }
+                            }
+                            if peers.read().is_empty() {
+                                p { class: "text-gray-500 flex items-center gap-2",
+                                    span { class: "loading loading-spinner loading-xs" }
+                                    r#"{t!("searching_devices")}"#
+                                }
+                            }
+                        }
+                    }
                     fieldset { class: "fieldset ",
-                        legend { class: "fieldset-legend text-gray-500", "IP" }
-                        input {
-                            class: "input input-lg",
-                            r#type: "text",
-                            placeholder: "192.168.1.1",
-                            value: "{ip_field.raw_value}",
-                            oninput: ip_field.oninput,
-                            onmounted: move |e| ip_field.mounted.set(Some(e)),
+                        legend { class: "fieldset-legend text-gray-500", r#"{t!("host")}"# }
+                        div { class: "flex gap-2",
+                            input {
+                                class: "input input-lg",
+                                r#type: "text",
+                                placeholder: "192.168.1.1",
+                                value: "{ip_field.raw_value}",
+                                oninput: ip_field.oninput,
+                                onmounted: move |e| ip_field.mounted.set(Some(e)),
+                            }
+                            div { class: "dropdown dropdown-end",
+                                div {
+                                    class: "btn btn-lg btn-square",
+                                    role: "button",
+                                    tabindex: "0",
+                                    title: r#"{t!("address_book")}"#,
+                                    svg {
+                                        class: "lucide lucide-book-user-icon lucide-book-user",
+                                        fill: "none",
+                                        height: "24",
+                                        stroke: "currentColor",
+                                        stroke_linecap: "round",
+                                        stroke_linejoin: "round",
+                                        stroke_width: "2",
+                                        view_box: "0 0 24 24",
+                                        width: "24",
+                                        xmlns: "http://www.w3.org/2000/svg",
+                                        path { d: "M15 13a3 3 0 1 0-6 0" }
+                                        path { d: "M4 19.5v-15A2.5 2.5 0 0 1 6.5 2H19a1 1 0 0 1 1 1v18a1 1 0 0 1-1 1H6.5a1 1 0 0 1 0-5H20" }
+                                        circle { cx: "12", cy: "8", r: "2" }
+                                    }
+                                }
+                                ul {
+                                    class: "dropdown-content menu bg-base-100 rounded-box shadow z-1 p-2 w-72 max-h-80 flex-nowrap overflow-y-auto",
+                                    tabindex: "-1",
+                                    li { class: "menu-title", r#"{t!("saved_peers")}"# }
+                                    for peer in address_book.read().peers.iter().cloned() {
+                                        li { key: "{peer.name}",
+                                            button {
+                                                class: "justify-between",
+                                                onclick: move |_| {
+                                                    fill_destination(peer.host.clone(), peer.port)
+                                                },
+                                                span { class: "truncate", "{peer.name}" }
+                                                span { class: "font-mono text-gray-500", "{peer.host}:{peer.port}" }
+                                            }
+                                        }
+                                    }
+                                    if address_book.read().peers.is_empty() {
+                                        li { class: "menu-disabled", span { r#"{t!("no_saved_peers")}"# } }
+                                    }
+                                    li { class: "menu-title", r#"{t!("recent_destinations")}"# }
+                                    for destination in address_book.read().recent.iter().cloned() {
+                                        li { key: "{destination.host}:{destination.port}",
+                                            button {
+                                                class: "font-mono",
+                                                onclick: move |_| fill_destination(destination.host.clone(), destination.port),
+                                                "{destination.host}:{destination.port}"
+                                            }
+                                        }
+                                    }
+                                    if address_book.read().recent.is_empty() {
+                                        li { class: "menu-disabled", span { r#"{t!("no_recent_destinations")}"# } }
+                                    }
+                                }
+                            }
                         }
                         p { class: "text-error", {ip_field.error} }
+                        if ip_field.error.read().is_none() {
+                            {resolved_hint}
+                        }
                     }
                     fieldset { class: "fieldset",
                         legend { class: "fieldset-legend text-gray-500", r#"{t!("port")}"# }
@@ -118,11 +337,37 @@ pub fn SenderPage() -> Element {
                             r#type: "number",
                             placeholder: "8000",
                             value: "{port_field.raw_value}",
+                            // 地址里已经带了端口
+                            disabled: ip_field.value.read().port.is_some(),
                             oninput: port_field.oninput,
                             onmounted: move |e| port_field.mounted.set(Some(e)),
                         }
                         p { class: "text-error", {port_field.error} }
                     }
+                    fieldset { class: "fieldset",
+                        legend { class: "fieldset-legend text-gray-500", r#"{t!("pairing_code")}"# }
+                        input {
+                            class: "input input-lg",
+                            r#type: "text",
+                            placeholder: "7-crimson-otter",
+                            value: "{pairing_code}",
+                            oninput: move |e| pairing_code.set(e.value()),
+                        }
+                    }
+                    fieldset { class: "fieldset",
+                        legend { class: "fieldset-legend text-gray-500", r#"{t!("send_limit")}"# }
+                        input {
+                            class: "input input-lg",
+                            r#type: "number",
+                            min: "0",
+                            step: "0.5",
+                            placeholder: r#"{t!("unlimited")}"#,
+                            value: "{rate_limit_field.raw_value}",
+                            oninput: rate_limit_field.oninput,
+                            onmounted: move |e| rate_limit_field.mounted.set(Some(e)),
+                        }
+                        p { class: "text-error", {rate_limit_field.error} }
+                    }
                     button {
                         class: "btn btn-info mt-8 px-20",
                         disabled: is_running.read().load(Relaxed),
@@ -131,30 +376,13 @@ pub fn SenderPage() -> Element {
                                 ip_field.focus().await;
                                 return;
                             }
-                            if port_field.error.read().is_some() {
+                            let target = ip_field.value.read().clone();
+                            if target.port.is_none() && port_field.error.read().is_some() {
                                 port_field.focus().await;
                                 return;
                             }
 
-                            is_running.write().store(true, Relaxed);
-                            progress.set((0.0, "0.00MB/s".to_string()));
-                            let running = is_running();
-                            let addr = format!("{}:{}", ip_field.value, port_field.value);
-                            let file = file();
-                            let log_tx = log_tx();
-                            let progress_tx = progress_tx();
-                            std::thread::spawn(move || {
-                                match handle_send(addr, file, log_tx.clone(), progress_tx) {
-                                    Ok(()) => {
-                                        running.store(false, Relaxed);
-                                        _ = log_tx.unbounded_send(t!("send_over").to_string());
-                                    }
-                                    Err(e) => {
-                                        running.store(false, Relaxed);
-                                        _ = log_tx.unbounded_send(format!("{} : {}", t!("send_fail"), e));
-                                    }
-                                }
-                            });
+                            start_send(target.with_default_port(*port_field.value.read()), file());
                         },
                         if is_running.read().load(Relaxed) {
                             r#"{t!("sending")}"#
@@ -163,6 +391,49 @@ pub fn SenderPage() -> Element {
                             r#"{t!("send")}"#
                         }
                     }
+                    if is_running.read().load(Relaxed) {
+                        button {
+                            class: "btn btn-warning mt-2 px-20",
+                            disabled: control.read().is_cancelled(),
+                            onclick: move |_| {
+                                let control = control.write();
+                                if control.is_paused() {
+                                    control.resume();
+                                    _ = log_tx.read().unbounded_send(t!("resumed").to_string());
+                                } else {
+                                    control.pause();
+                                    _ = log_tx.read().unbounded_send(t!("paused").to_string());
+                                }
+                            },
+                            if control.read().is_paused() {
+                                r#"{t!("continue")}"#
+                            } else {
+                                r#"{t!("pause")}"#
+                            }
+                        }
+                        button {
+                            class: "btn btn-error mt-2 px-20",
+                            disabled: control.read().is_cancelled(),
+                            onclick: move |_| {
+                                control.write().cancel();
+                                _ = log_tx.read().unbounded_send(t!("cancel").to_string());
+                            },
+                            r#"{t!("cancel")}"#
+                        }
+                    }
+                    if !is_running.read().load(Relaxed) && resumable.read().load(Relaxed) {
+                        if let Some((target, file)) = last_send() {
+                            button {
+                                class: "btn btn-warning mt-2 px-20",
+                                disabled: pairing_code.read().trim().is_empty(),
+                                onclick: move |_| start_send(target.clone(), file.clone()),
+                                r#"{t!("resume")}"#
+                            }
+                            if pairing_code.read().trim().is_empty() {
+                                p { class: "text-warning text-sm mt-1", r#"{t!("resume_needs_code")}"# }
+                            }
+                        }
+                    }
                 }
             }
             div { class: "h-1/3 flex gap-4",
@@ -170,11 +441,15 @@ pub fn SenderPage() -> Element {
                     div {
                         class: "radial-progress bg-blue-500 text-white border-blue-500 border-4",
                         role: "progressbar",
-                        aria_valuenow: "{progress.read().0:.0}",
-                        style: "--value:{progress.read().0:.0};",
-                        "{progress.read().0:.0}%"
+                        aria_valuenow: "{progress.read().percent():.0}",
+                        style: "--value:{progress.read().percent():.0};",
+                        "{progress.read().percent():.0}%"
+                    }
+                    if is_running.read().load(Relaxed) && control.read().is_paused() {
+                        p { class: "text-warning", r#"{t!("paused")}"# }
+                    } else {
+                        ProgressStats { progress: progress() }
                     }
-                    p { class: "text-gray-500", r#"{t!("speed")}: {progress.read().1}"# }
                 }
                 div { class: "flex-1 fieldset shadow rounded-box bg-base-100 px-4 flex relative",
                     div { class: "absolute -top-3 left-4 flex items-center gap-2",